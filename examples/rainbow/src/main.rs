@@ -21,6 +21,7 @@ along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
 
 use panic_halt as _;
 use smart_leds::hsv::{hsv2rgb, Hsv};
+use smart_leds::{SmartLedsWrite, RGB8};
 use ws2812_avr::{GRB, WS2812};
 
 const LED_COUNT: usize = 5;
@@ -30,8 +31,8 @@ fn main() -> ! {
     let dp = arduino_hal::Peripherals::take().unwrap();
     let pins = arduino_hal::pins!(dp);
 
-    let mut driver = WS2812::new(pins.d9.into_output());
-    let mut buf: [GRB; LED_COUNT] = [GRB::default(); LED_COUNT];
+    let mut driver = WS2812::new::<_, GRB>(pins.d9.into_output());
+    let mut buf: [RGB8; LED_COUNT] = [RGB8::default(); LED_COUNT];
 
     let mut cur_offset = 0;
     let mut hsv = Hsv {
@@ -41,13 +42,8 @@ fn main() -> ! {
     };
 
     loop {
-        let rgb = hsv2rgb(hsv);
-        buf[cur_offset] = GRB {
-            g: rgb.g,
-            r: rgb.r,
-            b: rgb.b,
-        };
-        driver.write(&buf);
+        buf[cur_offset] = hsv2rgb(hsv);
+        SmartLedsWrite::write(&mut driver, buf.iter().cloned()).unwrap();
         cur_offset = (cur_offset + 1) % LED_COUNT;
         hsv.hue += 1;
         arduino_hal::delay_ms(25);
@@ -0,0 +1,114 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Builds the modules of ws2812-avr that don't touch the hardware for
+ * the host, so that their unit tests can run there. The modules are
+ * included from the sources of the library, along with stand-ins for
+ * the parts of its crate root that they use. */
+#![allow(incomplete_features)]
+#![feature(const_trait_impl)]
+#![feature(never_type)]
+
+use core::marker::PhantomData;
+
+#[path = "../../ws2812-avr/src/adalight.rs"]
+pub mod adalight;
+#[path = "../../ws2812-avr/src/color.rs"]
+mod color;
+#[path = "../../ws2812-avr/src/dither.rs"]
+mod dither;
+#[path = "../../ws2812-avr/src/effects.rs"]
+pub mod effects;
+#[path = "../../ws2812-avr/src/graphics/mod.rs"]
+pub mod graphics;
+#[path = "../../ws2812-avr/src/math8.rs"]
+pub mod math8;
+#[path = "../../ws2812-avr/src/matrix.rs"]
+pub mod matrix;
+#[path = "../../ws2812-avr/src/palette.rs"]
+pub mod palette;
+#[path = "../../ws2812-avr/src/power.rs"]
+pub mod power;
+#[path = "../../ws2812-avr/src/segment.rs"]
+pub mod segment;
+#[path = "../../ws2812-avr/src/tpm2.rs"]
+pub mod tpm2;
+#[path = "../../ws2812-avr/src/transition.rs"]
+pub mod transition;
+
+// Paths in the util module below would be relative to a src/util
+// folder, which doesn't exist.
+#[path = "../../ws2812-avr/src/util/progmem.rs"]
+pub mod progmem;
+
+mod util {
+    pub use super::progmem;
+
+    mod sealed {
+        pub trait Sealed {}
+    }
+
+    pub(crate) use sealed::Sealed;
+}
+
+mod ports {
+    pub trait StaticPin {}
+}
+
+pub use color::*;
+
+pub trait TypedTimings {}
+
+/// Stand-in for the driver, recording the frames written to it.
+pub struct WS2812<P, Ts, Order> {
+    pub frames: Vec<Vec<Order>>,
+    _pin: PhantomData<(P, Ts)>,
+}
+
+impl<P, Ts, Order> Default for WS2812<P, Ts, Order> {
+    fn default() -> Self {
+        WS2812 {
+            frames: Vec::new(),
+            _pin: PhantomData,
+        }
+    }
+}
+
+impl<P, Ts, Order: Copy> WS2812<P, Ts, Order> {
+    pub fn write(&mut self, data: &[Order]) {
+        self.frames.push(data.to_vec());
+    }
+}
+
+/// Pin and timings for the driver stand-in.
+pub struct Dummy;
+
+impl ports::StaticPin for Dummy {}
+impl TypedTimings for Dummy {}
+
+/// Serial port that returns the bytes of a recorded stream, and then
+/// would block.
+pub struct Replay<'a>(pub &'a [u8]);
+
+impl embedded_hal::serial::Read<u8> for Replay<'_> {
+    type Error = !;
+
+    fn read(&mut self) -> nb::Result<u8, !> {
+        let (&byte, rest) = self.0.split_first().ok_or(nb::Error::WouldBlock)?;
+        self.0 = rest;
+        Ok(byte)
+    }
+}
@@ -0,0 +1,14 @@
+#!/bin/sh
+# Type-checks the library once for each supported MCU, using the
+# avr-spec that matches every feature. This ensures each feature
+# produces a valid set of pins, and that a single MCU is enabled.
+set -e
+
+cd "$(dirname "$0")/.."
+
+for spec in avr-specs/avr-*.json; do
+    mcu=$(basename "$spec" .json)
+    mcu=${mcu#avr-}
+    echo "Checking $mcu"
+    AVR_CPU_FREQUENCY_HZ=16000000 cargo check -p ws2812-avr --features "$mcu" --target "$spec"
+done
@@ -0,0 +1,9 @@
+#!/bin/sh
+# Runs the unit tests of the modules that don't touch the hardware on
+# the host. Arguments are passed to cargo test.
+set -e
+
+cd "$(dirname "$0")/../host-tests"
+
+host=$(rustc -vV | sed -n 's/^host: //p')
+cargo test --target "$host" "$@"
@@ -0,0 +1,85 @@
+#!/bin/sh
+# Runs the driver under simavr for every MCU it supports. For each
+# spec of the avr-specs folder, and for each CPU frequency, it builds
+# the firmware of tools/simavr/firmware, runs it tracing the writes
+# to the data pin into a VCD file, and decodes the waveform back into
+# pixels, checking them along with the timings of every preset. The
+# decoded waveform is written into another VCD file at cycle
+# resolution, to be opened with GTKWave. The MCUs to run can be given
+# through the MCUS environment variable, and the frequencies through
+# FREQUENCIES.
+#
+# The megaAVR-0 and tinyAVR-0/1/2 families are skipped when simavr does
+# not support their cores, which is the case of its releases so far,
+# so the driver is unverified on them. Any other MCU missing from
+# simavr, or not running any MCU at all, is a failure.
+set -e
+
+cd "$(dirname "$0")/.."
+root=$(pwd)
+out="$root/target/simavr"
+mkdir -p "$out"
+
+if ! command -v simavr >/dev/null; then
+    echo "simavr not found" >&2
+    exit 1
+fi
+
+host=$(rustc -vV | sed -n 's/^host: //p')
+cores=$(simavr --list-cores 2>&1 || true)
+frequencies=${FREQUENCIES:-"8000000 16000000"}
+allowed_skips="atmega4808 atmega4809 attiny804 attiny1614 attiny1616 attiny1624 attiny1626 attiny3216 attiny3224 attiny3226"
+failed=0
+ran=0
+skipped=""
+
+for spec in avr-specs/avr-*.json; do
+    mcu=$(basename "$spec" .json)
+    mcu=${mcu#avr-}
+    if [ -n "$MCUS" ] && ! echo "$MCUS" | grep -qw "$mcu"; then
+        continue
+    fi
+
+    # Data space address of the output register of the pin driven by
+    # the firmware, and the mask of the pin: PB1 on PORTB, or PA1 on
+    # VPORTA for the megaAVR-0 and tinyAVR-0/1/2 families.
+    case $mcu in
+        atmega8|atmega16|atmega32|attiny44|attiny84|attiny85|attiny2313) trace=0x38/0x02 ;;
+        atmega4808|atmega4809|attiny804|attiny1614|attiny1616|attiny1624|attiny1626|attiny3216|attiny3224|attiny3226) trace=0x01/0x02 ;;
+        *) trace=0x25/0x02 ;;
+    esac
+
+    if ! echo "$cores" | grep -qw "$mcu"; then
+        if echo "$allowed_skips" | grep -qw "$mcu"; then
+            echo "Skipping $mcu: not supported by simavr, unverified"
+            skipped="$skipped $mcu"
+        else
+            echo "Failed $mcu: not supported by simavr" >&2
+            failed=1
+        fi
+        continue
+    fi
+
+    for f_cpu in $frequencies; do
+        echo "Running $mcu at $f_cpu Hz"
+        (cd tools/simavr/firmware &&
+             AVR_CPU_FREQUENCY_HZ=$f_cpu cargo build -q --release --features "$mcu" --target "$root/$spec")
+
+        vcd="$out/$mcu-$f_cpu.vcd"
+        waveform="$out/$mcu-$f_cpu-waveform.vcd"
+        rm -f "$vcd" "$waveform"
+        timeout 60 simavr -m "$mcu" -f "$f_cpu" -o "$vcd" --add-trace "dout=trace@$trace" \
+                "tools/simavr/firmware/target/avr-$mcu/release/simavr-firmware.elf" >/dev/null 2>&1 || true
+
+        (cd tools/simavr/decode &&
+             cargo run -q --target "$host" -- --f-cpu "$f_cpu" --vcd-out "$waveform" "$vcd") || failed=1
+    done
+    ran=$((ran + 1))
+done
+
+echo "Ran $ran MCUs, not covered (unverified):${skipped:- none}"
+if [ $ran -eq 0 ]; then
+    echo "No MCU was run" >&2
+    failed=1
+fi
+exit $failed
@@ -0,0 +1,10 @@
+#!/bin/sh
+# Runs the host tool that prints the NOPs and timings generated for a
+# CPU frequency and a set of timings. Arguments are passed to the tool;
+# try --help.
+set -e
+
+cd "$(dirname "$0")/../tools/timings"
+
+host=$(rustc -vV | sed -n 's/^host: //p')
+cargo run -q --target "$host" -- "$@"
@@ -0,0 +1,332 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Decodes the data pin traced by simavr back into pixels, checking
+ * that the firmware wrote the pixels of the presets module once with
+ * each preset, and that every pulse took the cycles calculated by the
+ * ws2812-timings crate for it. The extra cycles between bytes come
+ * from the loop generated by the compiler, so they are measured from
+ * the first bytes of every frame, and the rest of the frame must keep
+ * them; a difference with the ones assumed by the frame time estimates
+ * is reported as a warning. It can also write the decoded waveform
+ * into a VCD file at cycle resolution, with the byte being sent and
+ * the latch period, for looking at what the driver did. */
+#[path = "../../presets.rs"]
+mod presets;
+mod vcd;
+
+use presets::{Preset, PIXELS, PRESETS};
+use std::process::exit;
+use vcd::Change;
+use ws2812_timings::{calculate, nanos_to_cycles, Calculated, FIXED_CYCLES_BYTE};
+
+const USAGE: &str = "\
+Usage: simavr-decode --f-cpu <HZ> [--signal <NAME>] [--vcd-out <FILE>] <VCD FILE>
+
+Options:
+    --f-cpu <HZ>        CPU frequency the firmware was built for
+    --signal <NAME>     Name of the traced data pin [default: dout]
+    --vcd-out <FILE>    Writes the traced waveform at cycle resolution,
+                        along with the bytes sent and the latch periods
+    -h, --help          Prints this help";
+
+/// Cycles the edges may be off by, as the dump rounds the time of
+/// every cycle.
+const TOLERANCE: u64 = 1;
+
+/// Shortest low time that splits two frames. It is longer than any
+/// bit, and shorter than any reset time.
+const FRAME_GAP_NANOS: u64 = 10_000;
+
+/// A pulse of the data pin, from the cycle at which it goes high to
+/// the one at which it goes low.
+#[derive(Clone, Copy)]
+struct Pulse {
+    rise: u64,
+    fall: u64,
+}
+
+fn near(value: u64, expected: u64) -> bool {
+    value.abs_diff(expected) <= TOLERANCE
+}
+
+/// Splits the changes of the pin into pulses, in CPU cycles.
+fn pulses(changes: &[(u128, bool)], f_cpu: u32) -> Vec<Pulse> {
+    let cycles =
+        |picos: u128| ((picos * f_cpu as u128 + 500_000_000_000) / 1_000_000_000_000) as u64;
+    let mut pulses = Vec::new();
+    let mut rise = None;
+    for &(time, high) in changes {
+        match (high, rise) {
+            (true, _) => rise = Some(cycles(time)),
+            (false, Some(start)) => {
+                pulses.push(Pulse {
+                    rise: start,
+                    fall: cycles(time),
+                });
+                rise = None;
+            }
+            (false, None) => {}
+        }
+    }
+    pulses
+}
+
+/**
+ * Extra cycles taken between the first two bytes of a frame, which
+ * depend on the code that the compiler generated for the driver.
+ */
+fn byte_gap(calculated: &Calculated, frame: &[Pulse]) -> Option<u64> {
+    let period = frame.get(8)?.rise - frame[7].rise;
+    Some(period.saturating_sub(calculated.bit_cycles() as u64))
+}
+
+/**
+ * Decodes the pulses of a frame written with `preset`, returning the
+ * errors found in them, if any. Every byte must start `byte_gap`
+ * cycles later than a bit would.
+ */
+fn check_frame(
+    preset: &Preset,
+    calculated: &Calculated,
+    byte_gap: u64,
+    frame: &[Pulse],
+    next: Option<&Pulse>,
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    let expected: Vec<u8> = PIXELS.iter().flatten().copied().collect();
+    if frame.len() != expected.len() * 8 {
+        errors.push(format!(
+            "{} bits sent, expected {}",
+            frame.len(),
+            expected.len() * 8
+        ));
+        return errors;
+    }
+
+    let t0h = calculated.achieved_t0h_cycles() as u64;
+    let t1h = calculated.achieved_t1h_cycles() as u64;
+    let mut bytes = vec![0u8; expected.len()];
+    for (i, pulse) in frame.iter().enumerate() {
+        let high = pulse.fall - pulse.rise;
+        if near(high, t1h) {
+            bytes[i / 8] |= 0x80 >> (i % 8);
+        } else if !near(high, t0h) {
+            errors.push(format!(
+                "bit {}: high for {} cycles, expected {} or {}",
+                i, high, t0h, t1h
+            ));
+        }
+
+        if i > 0 {
+            let period = pulse.rise - frame[i - 1].rise;
+            let mut expected = calculated.bit_cycles() as u64;
+            if i % 8 == 0 {
+                expected += byte_gap;
+            }
+            if !near(period, expected) {
+                errors.push(format!(
+                    "bit {}: started {} cycles after the previous one, expected {}",
+                    i, period, expected
+                ));
+            }
+        }
+    }
+
+    if bytes != expected {
+        errors.push(format!(
+            "decoded {:02x?}, expected {:02x?}",
+            bytes, expected
+        ));
+    }
+
+    if let (Some(last), Some(next)) = (frame.last(), next) {
+        let low = next.rise - last.fall;
+        if low + TOLERANCE < calculated.rst_cycles as u64 {
+            errors.push(format!(
+                "kept low for {} cycles after the frame, expected at least {} for a {} ns reset",
+                low, calculated.rst_cycles, preset.rst
+            ));
+        }
+    }
+
+    errors
+}
+
+/**
+ * Builds the waveform of the frames written with the presets, in CPU
+ * cycles. The bytes are decoded from the high time of every pulse,
+ * taking the ones longer than the middle point between the T0H and
+ * the T1H of its preset as ones, and the latch period of every frame
+ * lasts for the reset time of its preset, or until the next frame.
+ */
+fn waveform(frames: &[&[Pulse]], f_cpu: u32) -> Vec<(u64, Change)> {
+    let mut changes = Vec::new();
+    for (i, (preset, frame)) in PRESETS.iter().zip(frames).enumerate() {
+        let calculated = calculate(f_cpu, preset.t0h, preset.t1h, preset.cycle, preset.rst);
+        let threshold =
+            (calculated.achieved_t0h_cycles() as u64 + calculated.achieved_t1h_cycles() as u64) / 2;
+
+        for byte in frame.chunks(8) {
+            let value = byte.iter().fold(0u8, |value, pulse| {
+                (value << 1) | (pulse.fall - pulse.rise > threshold) as u8
+            });
+            changes.push((byte[0].rise, Change::Byte(value)));
+            for pulse in byte {
+                changes.push((pulse.rise, Change::High));
+                changes.push((pulse.fall, Change::Low));
+            }
+        }
+
+        if let Some(last) = frame.last() {
+            let end = last.fall + calculated.rst_cycles as u64;
+            let next = frames.get(i + 1).and_then(|frame| frame.first());
+            changes.push((last.fall, Change::Latch));
+            changes.push((next.map_or(end, |next| end.min(next.rise)), Change::End));
+        }
+    }
+    changes
+}
+
+struct Args {
+    f_cpu: u32,
+    signal: String,
+    vcd_out: Option<String>,
+    path: String,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut f_cpu = None;
+    let mut signal = String::from("dout");
+    let mut vcd_out = None;
+    let mut path = None;
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                exit(0);
+            }
+            "--f-cpu" => {
+                f_cpu = iter
+                    .next()
+                    .and_then(|value| value.parse::<u32>().ok())
+                    .filter(|&f| f > 0);
+                if f_cpu.is_none() {
+                    return Err("invalid CPU frequency".into());
+                }
+            }
+            "--signal" => {
+                signal = iter.next().ok_or("missing value for --signal")?;
+            }
+            "--vcd-out" => {
+                vcd_out = Some(iter.next().ok_or("missing value for --vcd-out")?);
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+
+    Ok(Args {
+        f_cpu: f_cpu.ok_or("missing --f-cpu")?,
+        signal,
+        vcd_out,
+        path: path.ok_or("missing VCD file")?,
+    })
+}
+
+fn main() {
+    let Args {
+        f_cpu,
+        signal,
+        vcd_out,
+        path,
+    } = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            exit(2);
+        }
+    };
+
+    let changes = match vcd::read_signal(&path, &signal) {
+        Ok(changes) => changes,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            exit(2);
+        }
+    };
+
+    let pulses = pulses(&changes, f_cpu);
+    let gap = nanos_to_cycles(f_cpu, FRAME_GAP_NANOS);
+    let mut frames: Vec<&[Pulse]> = Vec::new();
+    let mut start = 0;
+    for i in 1..=pulses.len() {
+        if i == pulses.len() || pulses[i].rise - pulses[i - 1].fall >= gap {
+            frames.push(&pulses[start..i]);
+            start = i;
+        }
+    }
+
+    if let Some(out) = vcd_out {
+        if let Err(err) = vcd::write(&out, f_cpu, &waveform(&frames, f_cpu)) {
+            eprintln!("error: cannot write {}: {}", out, err);
+            exit(2);
+        }
+    }
+
+    let mut failed = false;
+    if frames.len() != PRESETS.len() {
+        println!(
+            "{}: {} frames found, expected {}",
+            path,
+            frames.len(),
+            PRESETS.len()
+        );
+        failed = true;
+    }
+
+    for (i, (preset, frame)) in PRESETS.iter().zip(frames.iter()).enumerate() {
+        let calculated = calculate(f_cpu, preset.t0h, preset.t1h, preset.cycle, preset.rst);
+        let next = frames.get(i + 1).and_then(|frame| frame.first());
+        let gap = byte_gap(&calculated, frame).unwrap_or(0);
+        let errors = check_frame(preset, &calculated, gap, frame, next);
+        if errors.is_empty() {
+            println!(
+                "{}: {}: ok, {} cycles between bytes",
+                path, preset.name, gap
+            );
+            // The gap only affects the frame time estimates, as long
+            // as it is the same between every byte.
+            if gap != FIXED_CYCLES_BYTE as u64 {
+                println!(
+                    "{}: {}: warning: the frame time estimates assume {} cycles between bytes",
+                    path, preset.name, FIXED_CYCLES_BYTE
+                );
+            }
+        } else {
+            failed = true;
+            for error in errors {
+                println!("{}: {}: {}", path, preset.name, error);
+            }
+        }
+    }
+
+    if failed {
+        exit(1);
+    }
+}
@@ -0,0 +1,163 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Reads the changes of a signal from a Value Change Dump file, like
+ * the ones written by simavr when tracing the writes to a register,
+ * and writes the decoded waveform back into another one. */
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+
+/// Picoseconds in each of the units of the dump timescale.
+fn unit_picos(unit: &str) -> Option<u128> {
+    match unit {
+        "s" => Some(1_000_000_000_000),
+        "ms" => Some(1_000_000_000),
+        "us" => Some(1_000_000),
+        "ns" => Some(1_000),
+        "ps" => Some(1),
+        _ => None,
+    }
+}
+
+/**
+ * Returns the times, in picoseconds, at which the signal called
+ * `name` changes, along with its new level. Vector signals are high
+ * when any of their bits is set, and unknown values are taken as low.
+ */
+pub fn read_signal(path: &str, name: &str) -> Result<Vec<(u128, bool)>, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("cannot read {}: {}", path, err))?;
+    let mut tokens = text.split_whitespace();
+
+    let mut picos = 1;
+    let mut id = None;
+    while let Some(token) = tokens.next() {
+        match token {
+            "$timescale" => {
+                let scale: String = tokens.by_ref().take_while(|&t| t != "$end").collect();
+                let digits = scale.trim_end_matches(char::is_alphabetic);
+                picos = digits
+                    .parse::<u128>()
+                    .ok()
+                    .zip(unit_picos(&scale[digits.len()..]))
+                    .map(|(value, unit)| value * unit)
+                    .ok_or_else(|| format!("unsupported timescale: {}", scale))?;
+            }
+            "$var" => {
+                let var: Vec<&str> = tokens.by_ref().take_while(|&t| t != "$end").collect();
+                if var.len() >= 4 && var[3] == name {
+                    id = Some(var[2]);
+                }
+            }
+            "$enddefinitions" => break,
+            _ => {}
+        }
+    }
+
+    let id = id.ok_or_else(|| format!("no signal called {} in {}", name, path))?;
+    let mut changes: Vec<(u128, bool)> = Vec::new();
+    let mut time = 0;
+    while let Some(token) = tokens.next() {
+        let (value, token_id) = if let Some(stamp) = token.strip_prefix('#') {
+            time = stamp
+                .parse::<u128>()
+                .map_err(|_| format!("invalid timestamp: {}", token))?
+                * picos;
+            continue;
+        } else if let Some(bits) = token.strip_prefix(['b', 'B']) {
+            (bits.contains('1'), tokens.next().unwrap_or_default())
+        } else if token.starts_with(['0', '1', 'x', 'X', 'z', 'Z']) {
+            (token.starts_with('1'), &token[1..])
+        } else {
+            continue;
+        };
+
+        if token_id == id && changes.last().map(|&(_, high)| high) != Some(value) {
+            changes.push((time, value));
+        }
+    }
+
+    Ok(changes)
+}
+
+/// A change in the waveform written by [write], decoded from the trace.
+#[derive(Clone, Copy)]
+pub enum Change {
+    /// The driver starts sending the given byte.
+    Byte(u8),
+    /// The data pin goes high.
+    High,
+    /// The data pin goes low.
+    Low,
+    /// The last bit of a frame has been sent, and the pin is kept low
+    /// for the leds to latch it.
+    Latch,
+    /// The reset time of the frame is over.
+    End,
+}
+
+/**
+ * Writes the changes, given along with the CPU cycle at which they
+ * happen at `f_cpu` Hz, into the file at `path`. Along with the data
+ * pin, it has a signal with the byte being sent and another one that
+ * is high during the reset time, to find the byte boundaries and the
+ * latch period.
+ */
+pub fn write(path: &str, f_cpu: u32, changes: &[(u64, Change)]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let picos = |cycle: u64| cycle as u128 * 1_000_000_000_000 / f_cpu as u128;
+
+    writeln!(out, "$version simavr-decode $end")?;
+    writeln!(
+        out,
+        "$comment Data pin traced by simavr, at {} Hz $end",
+        f_cpu
+    )?;
+    writeln!(out, "$timescale 1 ps $end")?;
+    writeln!(out, "$scope module ws2812 $end")?;
+    writeln!(out, "$var wire 1 d dout $end")?;
+    writeln!(out, "$var reg 8 b byte $end")?;
+    writeln!(out, "$var wire 1 l latch $end")?;
+    writeln!(out, "$upscope $end")?;
+    writeln!(out, "$enddefinitions $end")?;
+    writeln!(out, "#0")?;
+    writeln!(out, "$dumpvars")?;
+    writeln!(out, "0d")?;
+    writeln!(out, "bx b")?;
+    writeln!(out, "0l")?;
+    writeln!(out, "$end")?;
+
+    let mut last = 0;
+    for &(cycle, change) in changes {
+        let time = picos(cycle);
+        if time != last {
+            writeln!(out, "#{}", time)?;
+            last = time;
+        }
+
+        match change {
+            Change::Byte(byte) => writeln!(out, "b{:08b} b", byte)?,
+            Change::High => writeln!(out, "1d")?,
+            Change::Low => writeln!(out, "0d")?,
+            Change::Latch => {
+                writeln!(out, "bx b")?;
+                writeln!(out, "1l")?;
+            }
+            Change::End => writeln!(out, "0l")?,
+        }
+    }
+
+    out.flush()
+}
@@ -0,0 +1,91 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Firmware run under simavr by `scripts/simavr-test.sh`. It writes
+ * the pixels of the presets module once with each preset, and then
+ * sleeps with interrupts disabled, which makes simavr quit. */
+#![no_std]
+#![no_main]
+#![allow(incomplete_features)]
+#![feature(asm_experimental_arch, generic_const_exprs)]
+
+#[path = "../../presets.rs"]
+mod presets;
+
+use core::arch::asm;
+use panic_halt as _;
+use presets::{PIXELS, PRESETS};
+use ws2812_avr::{util::time, Timings, GRB, WS2812};
+
+macro_rules! preset_timings {
+    ($($name:ident = $index:literal),*) => {
+	$(
+	    struct $name {}
+
+	    impl Timings for $name {
+		type Rst = time::Time<time::Nanos, { PRESETS[$index].rst }>;
+		type Cycle = time::Time<time::Nanos, { PRESETS[$index].cycle }>;
+		type T1h = time::Time<time::Nanos, { PRESETS[$index].t1h }>;
+		type T0h = time::Time<time::Nanos, { PRESETS[$index].t0h }>;
+	    }
+	)*
+    };
+}
+
+preset_timings!(Preset0 = 0, Preset1 = 1, Preset2 = 2);
+
+// Every driver takes a new handle to the same pin, which is fine as
+// they are used one after the other.
+#[cfg(feature = "atmega-hal")]
+macro_rules! output_pin {
+    () => {{
+        let dp = unsafe { atmega_hal::Peripherals::steal() };
+        atmega_hal::pins!(dp).pb1.into_output()
+    }};
+}
+
+#[cfg(feature = "attiny-hal")]
+macro_rules! output_pin {
+    () => {{
+        let dp = unsafe { attiny_hal::Peripherals::steal() };
+        attiny_hal::pins!(dp).pb1.into_output()
+    }};
+}
+
+#[cfg(feature = "port-b")]
+macro_rules! output_pin {
+    () => {
+        unsafe { ws2812_avr::pins::PB1::steal_output() }
+    };
+}
+
+#[cfg(feature = "port-a")]
+macro_rules! output_pin {
+    () => {
+        unsafe { ws2812_avr::pins::PA1::steal_output() }
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn main() -> ! {
+    let pixels = PIXELS.map(|[g, r, b]| GRB { g, r, b });
+
+    WS2812::new_with_timings::<Preset0, _, GRB>(output_pin!()).write(&pixels);
+    WS2812::new_with_timings::<Preset1, _, GRB>(output_pin!()).write(&pixels);
+    WS2812::new_with_timings::<Preset2, _, GRB>(output_pin!()).write(&pixels);
+
+    unsafe { asm!("cli", "sleep", options(noreturn)) }
+}
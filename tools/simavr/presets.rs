@@ -0,0 +1,64 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Timing presets and pixels shared by the firmware run under simavr
+ * and the decoder that checks its output. The firmware writes the
+ * pixels once with each preset, in order. */
+
+pub struct Preset {
+    pub name: &'static str,
+    pub t0h: u64,
+    pub t1h: u64,
+    pub cycle: u64,
+    pub rst: u64,
+}
+
+pub const PRESETS: [Preset; 3] = [
+    // DefaultTimings.
+    Preset {
+        name: "default",
+        t0h: 350,
+        t1h: 900,
+        cycle: 1250,
+        rst: 250_000,
+    },
+    // The timings of the custom-timings example, whose cycle is
+    // shorter than the fixed cycles of the loop at low frequencies.
+    Preset {
+        name: "ultra-fast",
+        t0h: 100,
+        t1h: 550,
+        cycle: 650,
+        rst: 50_000,
+    },
+    // The low speed mode of the WS2811.
+    Preset {
+        name: "ws2811-slow",
+        t0h: 500,
+        t1h: 1200,
+        cycle: 2500,
+        rst: 80_000,
+    },
+];
+
+/// The pixels written with every preset, in GRB order, which covers
+/// bytes with all their bits set, none of them and mixed ones.
+pub const PIXELS: [[u8; 3]; 4] = [
+    [0xff, 0x00, 0xa5],
+    [0x01, 0x80, 0x7e],
+    [0x00, 0x00, 0x00],
+    [0xff, 0xff, 0xff],
+];
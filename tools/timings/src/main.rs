@@ -0,0 +1,159 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Prints the NOPs and timings that ws2812-avr would generate for a
+ * CPU frequency and a set of timings, along with the led chips whose
+ * tolerances they meet. */
+
+use std::process::exit;
+use ws2812_timings::{calculate, nanos_to_cycles, Achieved, CHIPS, FIXED_CYCLES_TOTAL};
+
+const USAGE: &str = "\
+Usage: ws2812-timings [OPTIONS]
+
+Options (times in nanoseconds, defaults from DefaultTimings):
+    --f-cpu <HZ>     CPU frequency [default: 16000000]
+    --t0h <NS>       High time of a zero bit [default: 350]
+    --t1h <NS>       High time of a one bit [default: 900]
+    --cycle <NS>     Time of a whole bit [default: 1250]
+    --rst <NS>       Reset time [default: 250000]
+    -h, --help       Prints this help";
+
+struct Args {
+    f_cpu: u32,
+    t0h: u64,
+    t1h: u64,
+    cycle: u64,
+    rst: u64,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        f_cpu: 16_000_000,
+        t0h: 350,
+        t1h: 900,
+        cycle: 1250,
+        rst: 250_000,
+    };
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        if arg == "-h" || arg == "--help" {
+            println!("{}", USAGE);
+            exit(0);
+        }
+
+        let value = iter
+            .next()
+            .ok_or_else(|| format!("missing value for {}", arg))?;
+        let number = || {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("invalid value for {}: {}", arg, value))
+        };
+
+        match arg.as_str() {
+            "--f-cpu" => {
+                args.f_cpu = u32::try_from(number()?)
+                    .ok()
+                    .filter(|&f| f > 0)
+                    .ok_or_else(|| format!("invalid CPU frequency: {}", value))?
+            }
+            "--t0h" => args.t0h = number()?,
+            "--t1h" => args.t1h = number()?,
+            "--cycle" => args.cycle = number()?,
+            "--rst" => args.rst = number()?,
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
+
+    if args.t0h >= args.t1h {
+        return Err("T0h must be less than T1h".into());
+    }
+    if args.t1h >= args.cycle {
+        return Err("T1h must be less than the cycle".into());
+    }
+    if nanos_to_cycles(args.f_cpu, args.cycle) > u8::MAX as u64 {
+        return Err("the cycle takes more than 255 CPU cycles".into());
+    }
+
+    Ok(args)
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            exit(2);
+        }
+    };
+
+    let calculated = calculate(args.f_cpu, args.t0h, args.t1h, args.cycle, args.rst);
+    let achieved = Achieved::new(args.f_cpu, &calculated);
+
+    println!("F_CPU: {} Hz", args.f_cpu);
+    println!(
+        "NOPs (S1/S2/S3): {}/{}/{}",
+        calculated.s1_nops, calculated.s2_nops, calculated.s3_nops
+    );
+    println!("Cycles per bit: {}", calculated.bit_cycles());
+    if calculated.total_cycles < FIXED_CYCLES_TOTAL {
+        println!(
+            "warning: the cycle is shorter than the {} fixed cycles of the loop",
+            FIXED_CYCLES_TOTAL
+        );
+    }
+    println!();
+
+    println!("{:<10}{:>12}{:>12}", "", "achieved", "requested");
+    println!("{:<10}{:>9} ns{:>9} ns", "T0h", achieved.t0h, args.t0h);
+    println!("{:<10}{:>9} ns{:>9} ns", "T1h", achieved.t1h, args.t1h);
+    println!("{:<10}{:>9} ns", "T0l", achieved.t0l);
+    println!("{:<10}{:>9} ns", "T1l", achieved.t1l);
+    println!(
+        "{:<10}{:>9} ns{:>9} ns",
+        "Cycle", achieved.cycle, args.cycle
+    );
+    println!("{:<10}{:>9} ns{:>9} ns", "Reset", achieved.rst, args.rst);
+    println!(
+        "The low time of the last bit of each byte is {} ns longer.",
+        achieved.byte_gap
+    );
+    println!();
+
+    for chip in CHIPS.iter() {
+        let result = if chip.accepts(&achieved) {
+            "ok"
+        } else {
+            "FAIL"
+        };
+        println!(
+            "{:<10}{:<6}T0h {}-{}, T1h {}-{}, T0l {}-{}, T1l {}-{}, reset >= {}",
+            chip.name,
+            result,
+            chip.t0h.0,
+            chip.t0h.1,
+            chip.t1h.0,
+            chip.t1h.1,
+            chip.t0l.0,
+            chip.t0l.1,
+            chip.t1l.0,
+            chip.t1l.1,
+            chip.min_rst
+        );
+    }
+}
@@ -0,0 +1,413 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Receiver of the Adalight protocol, spoken by Ambilight-like
+ * software such as Prismatik or Hyperion for driving the backlight of
+ * a screen from a PC over serial. Every frame starts with a header
+ * made of the `Ada` magic word, the number of leds minus one as a big
+ * endian 16 bits number and a checksum of it, followed by the red,
+ * green and blue components of every led:
+ *
+ * ```text
+ * 'A' 'd' 'a' hi lo (hi ^ lo ^ 0x55) r g b r g b ...
+ * ```
+ *
+ * The parser is fed one byte at a time, so it works with any source
+ * of bytes, and it resynchronizes on the next header after corrupt
+ * headers. Sending a frame disables the interrupts for a while, during
+ * which the UART may lose bytes. A frame that lost some of its bytes
+ * runs into the header of the next one, which is recognized by its
+ * magic word and its number of leds, so the broken frame is dropped
+ * and the next one is received from its start. A header announcing a
+ * different number of leds in the middle of a frame is taken as data.
+ * A frame is complete as soon as its last color arrives. When its last
+ * colors look like the start of a header, the bytes that follow are
+ * taken as the rest of that header if they match it, so a frame that
+ * ran into the next header is still shown, but the next one is not
+ * lost.
+ *
+ * ```no_run
+ * let mut pixels = [GRB::default(); LED_COUNT];
+ * let mut receiver = Adalight::new(&mut pixels);
+ * for &byte in adalight::HELLO {
+ *     nb::block!(serial.write(byte)).ok();
+ * }
+ * loop {
+ *     nb::block!(receiver.update(&mut serial, &mut driver)).ok();
+ * }
+ * ```
+ */
+use crate::color::ColorOrder;
+use crate::ports::StaticPin;
+use crate::{TypedTimings, WS2812};
+use embedded_hal::serial::Read;
+
+/// Message sent by Adalight devices when they start, which some hosts
+/// wait for before sending frames.
+pub const HELLO: &[u8] = b"Ada\n";
+
+const MAGIC: &[u8] = b"Ada";
+
+#[derive(Clone, Copy)]
+enum State {
+    /// Looking for the magic word, with the number of its bytes
+    /// already matched.
+    Magic(u8),
+    CountHi,
+    CountLo(u8),
+    Checksum(u8, u8),
+    /// Reading the colors, with the number of bytes already read and
+    /// the number of bytes of a header for the same number of leds
+    /// seen at the end of them.
+    Data(usize, u8),
+}
+
+/**
+ * A parser of the Adalight protocol, that stores the colors of every
+ * frame into a buffer of pixels. Leds beyond the length of the buffer
+ * are read and dropped.
+ */
+pub struct Adalight<'a, O> {
+    pixels: &'a mut [O],
+    state: State,
+    leds: usize,
+    rgb: [u8; 3],
+    corrupt_headers: u16,
+}
+
+impl<'a, O: ColorOrder> Adalight<'a, O> {
+    pub fn new(pixels: &'a mut [O]) -> Self {
+        Adalight {
+            pixels,
+            state: State::Magic(0),
+            leds: 0,
+            rgb: [0; 3],
+            corrupt_headers: 0,
+        }
+    }
+
+    /// The pixels, holding the colors of the last frame received and,
+    /// while a frame is being received, some of the colors of it.
+    pub fn pixels(&self) -> &[O] {
+        self.pixels
+    }
+
+    /// Number of headers whose checksum did not match, which wraps
+    /// around.
+    pub fn corrupt_headers(&self) -> u16 {
+        self.corrupt_headers
+    }
+
+    /// Drops the frame being received, if any, waiting for the next
+    /// header.
+    pub fn reset(&mut self) {
+        self.state = State::Magic(0);
+    }
+
+    /**
+     * Parses the next byte of the stream. When it is the last byte of
+     * a frame, returns the number of leds of the frame that have been
+     * stored into the pixels.
+     */
+    pub fn push(&mut self, byte: u8) -> Option<usize> {
+        match self.state {
+            State::Magic(matched) => {
+                self.state = if byte == MAGIC[matched as usize] {
+                    if matched as usize + 1 == MAGIC.len() {
+                        State::CountHi
+                    } else {
+                        State::Magic(matched + 1)
+                    }
+                } else if byte == MAGIC[0] {
+                    State::Magic(1)
+                } else {
+                    State::Magic(0)
+                };
+            }
+            State::CountHi => self.state = State::CountLo(byte),
+            State::CountLo(hi) => self.state = State::Checksum(hi, byte),
+            State::Checksum(hi, lo) => {
+                if byte == hi ^ lo ^ 0x55 {
+                    self.leds = u16::from_be_bytes([hi, lo]) as usize + 1;
+                    self.state = State::Data(0, 0);
+                } else {
+                    // The magic word may have been a part of the data
+                    // of a truncated frame, so the next header may
+                    // start within the bytes taken as the count.
+                    self.corrupt_headers = self.corrupt_headers.wrapping_add(1);
+                    self.state = State::Magic(0);
+                    for byte in [hi, lo, byte] {
+                        self.push(byte);
+                    }
+                }
+            }
+            State::Data(read, header) => {
+                // Bytes lost while receiving the frame make it run into
+                // the header of the next one, which restarts it.
+                let header = if byte == self.header_byte(header) {
+                    header + 1
+                } else if byte == MAGIC[0] {
+                    1
+                } else {
+                    0
+                };
+                if header == 6 {
+                    self.state = State::Data(0, 0);
+                    return None;
+                }
+
+                self.rgb[read % 3] = byte;
+                if read % 3 == 2 {
+                    if let Some(pixel) = self.pixels.get_mut(read / 3) {
+                        *pixel = O::from_rgb(self.rgb[0], self.rgb[1], self.rgb[2]);
+                    }
+                }
+
+                if read + 1 < self.leds * 3 {
+                    self.state = State::Data(read + 1, header);
+                } else {
+                    // The frame may have run into the next header, so
+                    // the next bytes may continue the one seen so far.
+                    self.state = self.header_state(header);
+                    return Some(self.leds.min(self.pixels.len()));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns the state after the first `n` bytes of a header for the
+    /// number of leds of the frame being received.
+    fn header_state(&self, n: u8) -> State {
+        let [hi, lo] = ((self.leds - 1) as u16).to_be_bytes();
+        match n {
+            0..=2 => State::Magic(n),
+            3 => State::CountHi,
+            4 => State::CountLo(hi),
+            _ => State::Checksum(hi, lo),
+        }
+    }
+
+    /// Returns the `n`th byte of a header for the number of leds of
+    /// the frame being received.
+    fn header_byte(&self, n: u8) -> u8 {
+        let [hi, lo] = ((self.leds - 1) as u16).to_be_bytes();
+        match n {
+            0..=2 => MAGIC[n as usize],
+            3 => hi,
+            4 => lo,
+            _ => hi ^ lo ^ 0x55,
+        }
+    }
+
+    /**
+     * Reads the bytes available from `serial` until a frame is
+     * complete, returning the number of leds stored. Returns
+     * [nb::Error::WouldBlock] when no more bytes are available, so it
+     * can be called again later to continue with the same frame.
+     */
+    pub fn receive<S: Read<u8>>(&mut self, serial: &mut S) -> nb::Result<usize, S::Error> {
+        loop {
+            if let Some(leds) = self.push(serial.read()?) {
+                return Ok(leds);
+            }
+        }
+    }
+
+    /**
+     * Reads the bytes available from `serial` and, once a frame is
+     * complete, writes its leds into the driver.
+     */
+    pub fn update<S: Read<u8>, P: StaticPin, Ts: TypedTimings>(
+        &mut self,
+        serial: &mut S,
+        driver: &mut WS2812<P, Ts, O>,
+    ) -> nb::Result<(), S::Error> {
+        let leds = self.receive(serial)?;
+        driver.write(&self.pixels[..leds]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::RGB;
+    use crate::{Dummy, Replay};
+
+    /// Three leds, red, green and blue, as sent by Prismatik.
+    const FRAME: &[u8] = &[
+        b'A', b'd', b'a', 0x00, 0x02, 0x57, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0xFF,
+    ];
+
+    /// Dark red, dark green and dark blue, in another frame.
+    const NEXT_FRAME: &[u8] = &[
+        b'A', b'd', b'a', 0x00, 0x02, 0x57, 0x80, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00, 0x80,
+    ];
+
+    fn colors(pixels: &[RGB]) -> Vec<(u8, u8, u8)> {
+        pixels.iter().map(|pixel| pixel.to_rgb()).collect()
+    }
+
+    fn frame_colors(frame: &[u8]) -> Vec<(u8, u8, u8)> {
+        frame[6..].chunks(3).map(|c| (c[0], c[1], c[2])).collect()
+    }
+
+    #[test]
+    fn frames() {
+        let stream = [b"\x00\xFFA garbage Ad".as_slice(), FRAME, NEXT_FRAME].concat();
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Adalight::new(&mut pixels);
+        let mut serial = Replay(&stream);
+
+        assert_eq!(receiver.receive(&mut serial), Ok(3));
+        assert_eq!(colors(receiver.pixels()), frame_colors(FRAME));
+        assert_eq!(receiver.receive(&mut serial), Ok(3));
+        assert_eq!(colors(receiver.pixels()), frame_colors(NEXT_FRAME));
+        assert_eq!(receiver.receive(&mut serial), Err(nb::Error::WouldBlock));
+        assert_eq!(receiver.corrupt_headers(), 0);
+    }
+
+    #[test]
+    fn split_reads() {
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Adalight::new(&mut pixels);
+        let (first, second) = FRAME.split_at(8);
+
+        assert_eq!(
+            receiver.receive(&mut Replay(first)),
+            Err(nb::Error::WouldBlock)
+        );
+        assert_eq!(receiver.receive(&mut Replay(second)), Ok(3));
+        assert_eq!(colors(receiver.pixels()), frame_colors(FRAME));
+    }
+
+    #[test]
+    fn more_leds_than_pixels() {
+        let mut pixels = [RGB::default(); 2];
+        let mut receiver = Adalight::new(&mut pixels);
+        let stream = [FRAME, NEXT_FRAME].concat();
+        let mut serial = Replay(&stream);
+
+        assert_eq!(receiver.receive(&mut serial), Ok(2));
+        assert_eq!(colors(receiver.pixels()), frame_colors(FRAME)[..2]);
+        assert_eq!(receiver.receive(&mut serial), Ok(2));
+        assert_eq!(colors(receiver.pixels()), frame_colors(NEXT_FRAME)[..2]);
+    }
+
+    #[test]
+    fn corrupt_header() {
+        // The checksum of the first header is wrong.
+        let stream = [b"Ada\x00\x02\x56".as_slice(), FRAME].concat();
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Adalight::new(&mut pixels);
+
+        assert_eq!(receiver.receive(&mut Replay(&stream)), Ok(3));
+        assert_eq!(colors(receiver.pixels()), frame_colors(FRAME));
+        assert_eq!(receiver.corrupt_headers(), 1);
+    }
+
+    #[test]
+    fn header_within_corrupt_header() {
+        // A frame cut right after its magic word, so the next header
+        // starts in the bytes taken as its count and checksum.
+        let stream = [b"Ada".as_slice(), FRAME].concat();
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Adalight::new(&mut pixels);
+
+        assert_eq!(receiver.receive(&mut Replay(&stream)), Ok(3));
+        assert_eq!(colors(receiver.pixels()), frame_colors(FRAME));
+        assert_eq!(receiver.corrupt_headers(), 1);
+    }
+
+    #[test]
+    fn lost_bytes() {
+        // The first frame lost two bytes, so it runs into the second
+        // one, which must be received from its start.
+        let mut stream = [FRAME, NEXT_FRAME, FRAME].concat();
+        stream.drain(9..11);
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Adalight::new(&mut pixels);
+        let mut driver = WS2812::<Dummy, Dummy, RGB>::default();
+        let mut serial = Replay(&stream);
+
+        while receiver.update(&mut serial, &mut driver).is_ok() {}
+        let frames: Vec<_> = driver.frames.iter().map(|frame| colors(frame)).collect();
+        assert_eq!(frames[1..], [frame_colors(NEXT_FRAME), frame_colors(FRAME)]);
+        assert_eq!(frames.len(), 3);
+    }
+
+    #[test]
+    fn lost_bytes_before_header() {
+        // The first frame lost more bytes than a header takes, so the
+        // header is recognized within its colors, and it is dropped.
+        let mut stream = [FRAME, NEXT_FRAME, FRAME].concat();
+        stream.drain(7..13);
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Adalight::new(&mut pixels);
+        let mut driver = WS2812::<Dummy, Dummy, RGB>::default();
+        let mut serial = Replay(&stream);
+
+        while receiver.update(&mut serial, &mut driver).is_ok() {}
+        let frames: Vec<_> = driver.frames.iter().map(|frame| colors(frame)).collect();
+        assert_eq!(frames, [frame_colors(NEXT_FRAME), frame_colors(FRAME)]);
+    }
+
+    #[test]
+    fn many_lost_bytes() {
+        let mut stream = [FRAME, NEXT_FRAME].concat();
+        stream.drain(6..13);
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Adalight::new(&mut pixels);
+
+        assert_eq!(receiver.receive(&mut Replay(&stream)), Ok(3));
+        assert_eq!(colors(receiver.pixels()), frame_colors(NEXT_FRAME));
+    }
+
+    #[test]
+    fn frame_ending_like_header() {
+        // The last colors end like a header for the same number of
+        // leds, which must not hold the frame back.
+        for end in [b"A".as_slice(), b"Ad", b"Ada", b"Ada\x00", b"Ada\x00\x02"] {
+            let mut frame = b"Ada\x00\x02\x57\x10\x20\x30\x40".to_vec();
+            frame.extend_from_slice(&b"\x50\x60\x70\x80\x90"[..5 - end.len()]);
+            frame.extend_from_slice(end);
+            let mut pixels = [RGB::default(); 3];
+            let mut receiver = Adalight::new(&mut pixels);
+
+            assert_eq!(receiver.receive(&mut Replay(&frame)), Ok(3));
+            assert_eq!(colors(receiver.pixels()), frame_colors(&frame));
+
+            // A header following it is received whole.
+            let mut serial = Replay(NEXT_FRAME);
+            assert_eq!(receiver.receive(&mut serial), Ok(3));
+            assert_eq!(colors(receiver.pixels()), frame_colors(NEXT_FRAME));
+        }
+    }
+
+    #[test]
+    fn header_for_other_leds_is_data() {
+        // The colors of the second and third leds look like a header
+        // for 1 led, which doesn't match the frame.
+        let frame = b"Ada\x00\x02\x57\xFF\x00\x00Ada\x00\x00\x55";
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Adalight::new(&mut pixels);
+
+        assert_eq!(receiver.receive(&mut Replay(frame)), Ok(3));
+        assert_eq!(colors(receiver.pixels()), frame_colors(frame));
+    }
+}
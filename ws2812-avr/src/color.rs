@@ -16,7 +16,17 @@ along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
  */
 use crate::util::Sealed;
 
-pub trait ColorOrder: Sealed {}
+/**
+ * Defines the order in which the color components of each led are
+ * sent to the device.
+ */
+pub trait ColorOrder: Sealed + Copy {
+    /// Builds a color from its red, green and blue components.
+    fn from_rgb(r: u8, g: u8, b: u8) -> Self;
+
+    /// Returns the red, green and blue components of the color.
+    fn to_rgb(self) -> (u8, u8, u8);
+}
 
 #[repr(C)]
 #[derive(Default, Clone, Copy)]
@@ -43,10 +53,480 @@ pub struct BGR {
 }
 
 impl Sealed for RGB {}
-impl ColorOrder for RGB {}
+impl const ColorOrder for RGB {
+    #[inline(always)]
+    fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        RGB { r, g, b }
+    }
+
+    #[inline(always)]
+    fn to_rgb(self) -> (u8, u8, u8) {
+        (self.r, self.g, self.b)
+    }
+}
 
 impl Sealed for GRB {}
-impl ColorOrder for GRB {}
+impl const ColorOrder for GRB {
+    #[inline(always)]
+    fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        GRB { r, g, b }
+    }
+
+    #[inline(always)]
+    fn to_rgb(self) -> (u8, u8, u8) {
+        (self.r, self.g, self.b)
+    }
+}
 
 impl Sealed for BGR {}
-impl ColorOrder for BGR {}
+impl const ColorOrder for BGR {
+    #[inline(always)]
+    fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        BGR { r, g, b }
+    }
+
+    #[inline(always)]
+    fn to_rgb(self) -> (u8, u8, u8) {
+        (self.r, self.g, self.b)
+    }
+}
+
+/**
+ * A color in the HSV color space, using 8 bits for each component.
+ * The hue covers the full color circle in the range 0-255.
+ */
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub struct Hsv8 {
+    pub h: u8,
+    pub s: u8,
+    pub v: u8,
+}
+
+/**
+ * A color in the HSL color space, using 8 bits for each component.
+ * The hue covers the full color circle in the range 0-255.
+ */
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub struct Hsl8 {
+    pub h: u8,
+    pub s: u8,
+    pub l: u8,
+}
+
+impl Hsv8 {
+    pub const fn new(h: u8, s: u8, v: u8) -> Self {
+        Hsv8 { h, s, v }
+    }
+
+    /**
+     * Converts the color using the "rainbow" hue mapping from FastLED,
+     * that gives the yellow band the same width as the rest of main
+     * colors. It tends to look more natural on leds than the
+     * [Hsv8::to_spectrum] mapping.
+     */
+    pub const fn to_rainbow<O: ~const ColorOrder>(self) -> O {
+        // The channels are computed as 8.8 fixed point numbers, and
+        // rounded only once at the end.
+        let hue = self.h;
+        let offset = (hue & 0x1F) as u16;
+        let third = offset * (85 << 3);
+        let twothirds = offset * (170 << 3);
+
+        let (r, g, b): (u16, u16, u16) = if hue & 0x80 == 0 {
+            if hue & 0x40 == 0 {
+                if hue & 0x20 == 0 {
+                    // Red to orange.
+                    ((255 << 8) - third, third, 0)
+                } else {
+                    // Orange to yellow.
+                    (171 << 8, (85 << 8) + third, 0)
+                }
+            } else if hue & 0x20 == 0 {
+                // Yellow to green.
+                ((171 << 8) - twothirds, (170 << 8) + third, 0)
+            } else {
+                // Green to aqua.
+                (0, (255 << 8) - third, third)
+            }
+        } else if hue & 0x40 == 0 {
+            if hue & 0x20 == 0 {
+                // Aqua to blue.
+                (0, (171 << 8) - twothirds, (85 << 8) + twothirds)
+            } else {
+                // Blue to purple.
+                (third, 0, (255 << 8) - third)
+            }
+        } else if hue & 0x20 == 0 {
+            // Purple to pink.
+            ((85 << 8) + third, 0, (171 << 8) - third)
+        } else {
+            // Pink to red.
+            ((170 << 8) + third, 0, (85 << 8) - third)
+        };
+
+        // Saturation and value follow quadratic curves, like in
+        // FastLED, which look more even on leds than linear ones.
+        let desat = square16(255 - self.s);
+        let val = square16(self.v);
+        O::from_rgb(
+            shade(r, desat, val),
+            shade(g, desat, val),
+            shade(b, desat, val),
+        )
+    }
+
+    /**
+     * Converts the color using the "spectrum" hue mapping from
+     * FastLED, where red, green and blue are spaced evenly along the
+     * hue range, and each one fades linearly into the next one,
+     * keeping the total brightness constant.
+     */
+    pub const fn to_spectrum<O: ~const ColorOrder>(self) -> O {
+        // Each third of the hue range is a section, and `offset` is the
+        // position within it, out of 256.
+        let position = self.h as u16 * 3;
+        let section = position >> 8;
+        let offset = position & 0xFF;
+
+        let brightness_floor = mul8(self.v, 255 - self.s);
+        let color_amplitude = self.v - brightness_floor;
+        let rampup = ((color_amplitude as u16 * offset + 0x80) >> 8) as u8;
+        let rampup_adj = brightness_floor + rampup;
+        let rampdown_adj = self.v - rampup;
+
+        match section {
+            0 => O::from_rgb(rampdown_adj, rampup_adj, brightness_floor),
+            1 => O::from_rgb(brightness_floor, rampdown_adj, rampup_adj),
+            _ => O::from_rgb(rampup_adj, brightness_floor, rampdown_adj),
+        }
+    }
+}
+
+/// Returns `(x / 255)²` as a 0.16 fixed point fraction.
+const fn square16(x: u8) -> u16 {
+    let square = x as u16 * x as u16;
+    square + (square >> 7)
+}
+
+/// Returns `a * b / 255`, rounded to the nearest integer.
+const fn mul8(a: u8, b: u8) -> u8 {
+    let product = a as u16 * b as u16 + 0x80;
+    ((product + (product >> 8)) >> 8) as u8
+}
+
+/// Multiplies `a` by the 0.16 fixed point fraction `f`.
+const fn mul16(a: u16, f: u16) -> u16 {
+    ((a as u32 * f as u32) >> 16) as u16
+}
+
+/**
+ * Mixes the 8.8 fixed point channel `c` with white by `desat`, scales
+ * it by `val` and rounds it to 8 bits.
+ */
+const fn shade(c: u16, desat: u16, val: u16) -> u8 {
+    let c = c + mul16((255 << 8) - c, desat);
+    ((mul16(c, val) + 0x80) >> 8) as u8
+}
+
+impl Hsl8 {
+    pub const fn new(h: u8, s: u8, l: u8) -> Self {
+        Hsl8 { h, s, l }
+    }
+
+    /// Converts the color into the HSV color space.
+    pub const fn to_hsv(self) -> Hsv8 {
+        let l = self.l as u16;
+        let s = self.s as u16;
+        let chroma_half = if l < 128 { l } else { 255 - l };
+        let v = l + (s * chroma_half + 127) / 255;
+        let s = if v == 0 {
+            0
+        } else {
+            (2 * (v - l) * 255 + v / 2) / v
+        };
+
+        Hsv8 {
+            h: self.h,
+            s: if s > 255 { 255 } else { s as u8 },
+            v: v as u8,
+        }
+    }
+
+    /// Converts the color using the "rainbow" hue mapping. See [Hsv8::to_rainbow].
+    pub const fn to_rainbow<O: ~const ColorOrder>(self) -> O {
+        self.to_hsv().to_rainbow()
+    }
+
+    /// Converts the color using the "spectrum" hue mapping. See [Hsv8::to_spectrum].
+    pub const fn to_spectrum<O: ~const ColorOrder>(self) -> O {
+        self.to_hsv().to_spectrum()
+    }
+}
+
+impl const From<Hsl8> for Hsv8 {
+    fn from(color: Hsl8) -> Self {
+        color.to_hsv()
+    }
+}
+
+/**
+ * Returns the color at the given position of a color wheel, that
+ * transitions from red to green, then to blue and back to red. All
+ * the colors have the same total brightness.
+ */
+pub const fn wheel<O: ~const ColorOrder>(pos: u8) -> O {
+    let pos = 255 - pos;
+    if pos < 85 {
+        O::from_rgb(255 - pos * 3, 0, pos * 3)
+    } else if pos < 170 {
+        let pos = pos - 85;
+        O::from_rgb(0, pos * 3, 255 - pos * 3)
+    } else {
+        let pos = pos - 170;
+        O::from_rgb(pos * 3, 255 - pos * 3, 0)
+    }
+}
+
+macro_rules! hsv_conversions {
+    ($($color:ident),*) => {
+	$(
+	    impl const From<Hsv8> for $color {
+		fn from(color: Hsv8) -> Self {
+		    color.to_rainbow()
+		}
+	    }
+
+	    impl const From<Hsl8> for $color {
+		fn from(color: Hsl8) -> Self {
+		    color.to_rainbow()
+		}
+	    }
+	)*
+    };
+}
+
+hsv_conversions!(RGB, GRB, BGR);
+
+#[cfg(feature = "rgb")]
+mod rgb_interop {
+    use super::{BGR, GRB, RGB};
+    use core::mem::{align_of, size_of};
+    use core::slice;
+    use rgb::RGB8;
+
+    macro_rules! rgb8_conversions {
+	($($color:ident),*) => {
+	    $(
+		impl From<RGB8> for $color {
+		    #[inline(always)]
+		    fn from(color: RGB8) -> Self {
+			$color {
+			    r: color.r,
+			    g: color.g,
+			    b: color.b,
+			}
+		    }
+		}
+
+		impl From<$color> for RGB8 {
+		    #[inline(always)]
+		    fn from(color: $color) -> Self {
+			RGB8::new(color.r, color.g, color.b)
+		    }
+		}
+	    )*
+	};
+    }
+
+    rgb8_conversions!(RGB, GRB, BGR);
+
+    // Both types are #[repr(C)] structs with three u8 fields in the
+    // same order, which is what makes the casts below sound.
+    const _: () = assert!(size_of::<RGB>() == size_of::<RGB8>());
+    const _: () = assert!(align_of::<RGB>() == align_of::<RGB8>());
+
+    impl RGB {
+        /// Reinterprets a slice of [RGB8] colors as a slice of [RGB] colors, without copying.
+        pub fn from_rgb8_slice(colors: &[RGB8]) -> &[RGB] {
+            // SAFETY: RGB and RGB8 share the same layout.
+            unsafe { slice::from_raw_parts(colors.as_ptr() as *const RGB, colors.len()) }
+        }
+
+        /// Reinterprets a mutable slice of [RGB8] colors as a mutable slice of [RGB] colors, without copying.
+        pub fn from_rgb8_slice_mut(colors: &mut [RGB8]) -> &mut [RGB] {
+            // SAFETY: RGB and RGB8 share the same layout.
+            unsafe { slice::from_raw_parts_mut(colors.as_mut_ptr() as *mut RGB, colors.len()) }
+        }
+
+        /// Reinterprets a slice of [RGB] colors as a slice of [RGB8] colors, without copying.
+        pub fn as_rgb8_slice(colors: &[RGB]) -> &[RGB8] {
+            // SAFETY: RGB and RGB8 share the same layout.
+            unsafe { slice::from_raw_parts(colors.as_ptr() as *const RGB8, colors.len()) }
+        }
+
+        /// Reinterprets a mutable slice of [RGB] colors as a mutable slice of [RGB8] colors, without copying.
+        pub fn as_rgb8_slice_mut(colors: &mut [RGB]) -> &mut [RGB8] {
+            // SAFETY: RGB and RGB8 share the same layout.
+            unsafe { slice::from_raw_parts_mut(colors.as_mut_ptr() as *mut RGB8, colors.len()) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks every channel of `color` against `expected`, within one step.
+    fn assert_close(color: RGB, expected: [f32; 3], input: (u8, u8, u8)) {
+        let (r, g, b) = color.to_rgb();
+        for (channel, reference) in [r, g, b].into_iter().zip(expected) {
+            assert!(
+                (channel as f32 - reference).abs() <= 1.0,
+                "{:?} gave {:?}, expected {:?}",
+                input,
+                (r, g, b),
+                expected
+            );
+        }
+    }
+
+    /// The colors at the start of each eighth of the rainbow, and how
+    /// much they change along it.
+    fn rainbow_ref(h: u8, s: u8, v: u8) -> [f32; 3] {
+        const START: [[f32; 3]; 8] = [
+            [255.0, 0.0, 0.0],
+            [171.0, 85.0, 0.0],
+            [171.0, 170.0, 0.0],
+            [0.0, 255.0, 0.0],
+            [0.0, 171.0, 85.0],
+            [0.0, 0.0, 255.0],
+            [85.0, 0.0, 171.0],
+            [170.0, 0.0, 85.0],
+        ];
+        const DELTA: [[f32; 3]; 8] = [
+            [-85.0, 85.0, 0.0],
+            [0.0, 85.0, 0.0],
+            [-170.0, 85.0, 0.0],
+            [0.0, -85.0, 85.0],
+            [0.0, -170.0, 170.0],
+            [85.0, 0.0, -85.0],
+            [85.0, 0.0, -85.0],
+            [85.0, 0.0, -85.0],
+        ];
+
+        let section = (h >> 5) as usize;
+        let offset = (h & 0x1F) as f32 / 32.0;
+        let desat = ((255 - s) as f32 / 255.0).powi(2);
+        let val = (v as f32 / 255.0).powi(2);
+        let mut color = [0.0; 3];
+        for (i, channel) in color.iter_mut().enumerate() {
+            let hue = START[section][i] + DELTA[section][i] * offset;
+            *channel = (hue + (255.0 - hue) * desat) * val;
+        }
+        color
+    }
+
+    /// Red, green and blue fading linearly into each other.
+    fn spectrum_ref(h: u8, s: f32, v: f32) -> [f32; 3] {
+        let position = h as f32 * 3.0 / 256.0;
+        let offset = position.fract();
+        let floor = v * (1.0 - s / 255.0);
+        let rampup = floor + (v - floor) * offset;
+        let rampdown = floor + (v - floor) * (1.0 - offset);
+        match position as u8 {
+            0 => [rampdown, rampup, floor],
+            1 => [floor, rampdown, rampup],
+            _ => [rampup, floor, rampdown],
+        }
+    }
+
+    /// Converts the saturation and lightness of an HSL color into the
+    /// saturation and value of an HSV one.
+    fn hsl_ref(s: u8, l: u8) -> (f32, f32) {
+        let s = s as f32 / 255.0;
+        let l = l as f32 / 255.0;
+        let v = l + s * l.min(1.0 - l);
+        let s = if v == 0.0 { 0.0 } else { 2.0 * (1.0 - l / v) };
+        (s * 255.0, v * 255.0)
+    }
+
+    #[test]
+    fn rainbow() {
+        for h in 0..=255 {
+            for s in 0..=255 {
+                for v in 0..=255 {
+                    let color = Hsv8::new(h, s, v).to_rainbow();
+                    assert_close(color, rainbow_ref(h, s, v), (h, s, v));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rainbow_keeps_primaries() {
+        assert_eq!(
+            Hsv8::new(0, 255, 255).to_rainbow::<RGB>().to_rgb(),
+            (255, 0, 0)
+        );
+        assert_eq!(
+            Hsv8::new(96, 255, 255).to_rainbow::<RGB>().to_rgb(),
+            (0, 255, 0)
+        );
+        assert_eq!(
+            Hsv8::new(160, 255, 255).to_rainbow::<RGB>().to_rgb(),
+            (0, 0, 255)
+        );
+        assert_eq!(
+            Hsv8::new(42, 0, 255).to_rainbow::<RGB>().to_rgb(),
+            (255, 255, 255)
+        );
+        assert_eq!(
+            Hsv8::new(42, 255, 0).to_rainbow::<RGB>().to_rgb(),
+            (0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn spectrum() {
+        for h in 0..=255 {
+            for s in 0..=255 {
+                for v in 0..=255 {
+                    let color = Hsv8::new(h, s, v).to_spectrum();
+                    let expected = spectrum_ref(h, s as f32, v as f32);
+                    assert_close(color, expected, (h, s, v));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hsl() {
+        for h in 0..=255 {
+            for s in 0..=255 {
+                for l in 0..=255 {
+                    let (sv, v) = hsl_ref(s, l);
+                    let hsv = Hsl8::new(h, s, l).to_hsv();
+                    assert!((hsv.v as f32 - v).abs() <= 1.0, "{:?}", (h, s, l));
+                    let color = Hsl8::new(h, s, l).to_spectrum();
+                    assert_close(color, spectrum_ref(h, sv, v), (h, s, l));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wheel_positions() {
+        for pos in 0..=255u8 {
+            // The wheel runs backwards, from red to blue and green,
+            // and the last section ends at red again.
+            let section = ((255 - pos) / 85).min(2);
+            let offset = (255 - pos - section * 85) as f32 * 3.0;
+            let expected = match section {
+                0 => [255.0 - offset, 0.0, offset],
+                1 => [0.0, offset, 255.0 - offset],
+                _ => [offset, 255.0 - offset, 0.0],
+            };
+            assert_close(wheel(pos), expected, (pos, 0, 0));
+        }
+    }
+}
@@ -0,0 +1,159 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Temporal dithering for brightness-scaled frames, modeled after the
+ * one from FastLED. When a frame is dimmed, the fractional part of
+ * each scaled value is lost, which shows as visible steps on slow
+ * fades at low brightness. Dithering adds an offset to each scaled
+ * value before dropping its fractional part, so that it is rounded up
+ * on some frames and down on others, and the average over several
+ * frames matches the exact value. */
+
+/// Number of frames in a dithering cycle, as a power of two.
+const DITHER_BITS: u8 = 3;
+
+/**
+ * Per-frame dithering state kept by the driver.
+ */
+#[derive(Clone, Copy)]
+pub(crate) struct Dither {
+    enabled: bool,
+    frame: u8,
+}
+
+impl Dither {
+    pub(crate) const fn new() -> Self {
+        Dither {
+            enabled: false,
+            frame: 0,
+        }
+    }
+
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /**
+     * Advances to the next frame and returns the initial offset to pass
+     * to [scale8_dithered], along with the value the offset must be
+     * subtracted from after each pixel, so that it alternates between
+     * neighbouring pixels. Both are zero when dithering is disabled.
+     */
+    pub(crate) fn next_frame(&mut self) -> (u8, u8) {
+        if !self.enabled {
+            return (0, 0);
+        }
+
+        self.frame = (self.frame + 1) & ((1 << DITHER_BITS) - 1);
+
+        // The frame counter is bit-reversed, so consecutive frames are
+        // spread as far as possible, and centered on its interval.
+        let mut offset = 0x10;
+        if self.frame & 0x01 != 0 {
+            offset |= 0x80;
+        }
+        if self.frame & 0x02 != 0 {
+            offset |= 0x40;
+        }
+        if self.frame & 0x04 != 0 {
+            offset |= 0x20;
+        }
+
+        (offset, 0xFF)
+    }
+}
+
+/// Scales `i` by `scale / 256` like [crate::math8::scale8], adding
+/// `offset / 256` before rounding the result down. A scale of zero
+/// turns every value off, whatever the offset.
+#[inline(always)]
+pub(crate) fn scale8_dithered(i: u8, scale: u8, offset: u8) -> u8 {
+    if scale == 0 {
+        return 0;
+    }
+
+    ((i as u16 * (1 + scale as u16) + offset as u16) >> 8) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Offsets of the first two pixels of the frames of a dithering
+    /// cycle.
+    fn cycle_offsets() -> Vec<[u8; 2]> {
+        let mut dither = Dither::new();
+        dither.set_enabled(true);
+        (0..1 << DITHER_BITS)
+            .map(|_| {
+                let (offset, toggle) = dither.next_frame();
+                [offset, toggle - offset]
+            })
+            .collect()
+    }
+
+    /// Sums the values that `i` is scaled to over a dithering cycle,
+    /// for the first two pixels of the frames.
+    fn cycle_sums(i: u8, scale: u8) -> [u32; 2] {
+        let offsets = cycle_offsets();
+        [0, 1].map(|pixel| {
+            offsets
+                .iter()
+                .map(|offsets| scale8_dithered(i, scale, offsets[pixel]) as u32)
+                .sum()
+        })
+    }
+
+    #[test]
+    fn averages_at_low_brightness() {
+        let frames = 1 << DITHER_BITS;
+        for scale in 1..32 {
+            for i in 0..=255 {
+                // The exact value, in the same scale as scale8, times
+                // the number of frames.
+                let exact = i as u32 * (1 + scale as u32) * frames;
+                for sum in cycle_sums(i, scale) {
+                    // The average is within half a step of the
+                    // dithering offsets of the exact value.
+                    let error = (sum * 256).abs_diff(exact);
+                    assert!(error <= 256 / 2, "{i} * {scale}: {sum}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn exact_at_full_and_zero_brightness() {
+        for i in 0..=255 {
+            assert_eq!(cycle_sums(i, 255), [i as u32 * 8; 2]);
+            assert_eq!(cycle_sums(i, 0), [0; 2]);
+        }
+    }
+
+    #[test]
+    fn disabled() {
+        let mut dither = Dither::new();
+        assert_eq!(dither.next_frame(), (0, 0));
+        for i in 0..=255 {
+            for scale in 0..=255 {
+                assert_eq!(
+                    scale8_dithered(i, scale, 0),
+                    crate::math8::scale8_const(i, scale)
+                );
+            }
+        }
+    }
+}
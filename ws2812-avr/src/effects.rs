@@ -0,0 +1,466 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Ready-made animations. Each effect is a small struct holding its
+ * settings and state, that renders a frame into a buffer on every call
+ * to [Effect::step], given the number of the frame. None of them
+ * allocate memory, and they can be used on a whole strip or on any of
+ * its segments. */
+use crate::color::{ColorOrder, Hsv8};
+use crate::math8::{qadd8, qsub8, scale8_video, sin8, ColorMath};
+
+/**
+ * An animation that renders its frames into a buffer.
+ */
+pub trait Effect<O> {
+    /// Renders the frame number `tick` into the buffer, which may keep
+    /// the contents of the previous frame.
+    fn step(&mut self, pixels: &mut [O], tick: u32);
+}
+
+/**
+ * A tiny pseudo-random number generator, the same used by FastLED,
+ * which is good enough for animations.
+ */
+#[derive(Clone, Copy)]
+pub struct Rng {
+    seed: u16,
+}
+
+impl Rng {
+    pub const fn new(seed: u16) -> Self {
+        Rng { seed }
+    }
+
+    /// Returns a random value.
+    pub fn next_u8(&mut self) -> u8 {
+        self.seed = self.seed.wrapping_mul(2053).wrapping_add(13849);
+        // The high byte is more random than the low one, so both are
+        // mixed together.
+        ((self.seed & 0xFF) as u8).wrapping_add((self.seed >> 8) as u8)
+    }
+
+    /// Returns a random value lower than `max`.
+    pub fn below(&mut self, max: u8) -> u8 {
+        ((self.next_u8() as u16 * max as u16) >> 8) as u8
+    }
+
+    /// Returns a random value from `min` (included) to `max` (excluded).
+    /// The bounds are swapped if `max` is lower than `min`, and `min`
+    /// is returned if they are equal.
+    pub fn between(&mut self, min: u8, max: u8) -> u8 {
+        let (min, max) = if max < min { (max, min) } else { (min, max) };
+        min + self.below(max - min)
+    }
+}
+
+/**
+ * A rainbow that moves along the strip. The hue of consecutive leds
+ * differs by `delta_hue`, and is increased by `speed` on every frame.
+ */
+pub struct RainbowCycle {
+    pub delta_hue: u8,
+    pub speed: u8,
+}
+
+impl RainbowCycle {
+    pub const fn new(delta_hue: u8, speed: u8) -> Self {
+        RainbowCycle { delta_hue, speed }
+    }
+}
+
+impl<O: ColorOrder> Effect<O> for RainbowCycle {
+    fn step(&mut self, pixels: &mut [O], tick: u32) {
+        let mut hue = (tick as u8).wrapping_mul(self.speed);
+        for pixel in pixels {
+            *pixel = Hsv8::new(hue, 255, 255).to_rainbow();
+            hue = hue.wrapping_add(self.delta_hue);
+        }
+    }
+}
+
+/**
+ * Lights one of every `spacing` leds, moving the lit ones by one led
+ * on every frame, like the lights around a theater marquee.
+ */
+pub struct TheaterChase<O> {
+    pub color: O,
+    pub background: O,
+    pub spacing: u8,
+}
+
+impl<O: ColorOrder> TheaterChase<O> {
+    pub fn new(color: O, spacing: u8) -> Self {
+        TheaterChase {
+            color,
+            background: O::from_rgb(0, 0, 0),
+            spacing,
+        }
+    }
+}
+
+impl<O: ColorOrder> Effect<O> for TheaterChase<O> {
+    fn step(&mut self, pixels: &mut [O], tick: u32) {
+        let spacing = self.spacing.max(1) as u32;
+        let phase = tick % spacing;
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            *pixel = if i as u32 % spacing == phase {
+                self.color
+            } else {
+                self.background
+            };
+        }
+    }
+}
+
+/**
+ * A led that runs along the strip, leaving behind a tail that fades
+ * out by `fade` on every frame. The head moves one led every frame,
+ * going back to the start after reaching the end of the strip.
+ */
+pub struct Comet<O> {
+    pub color: O,
+    pub fade: u8,
+}
+
+impl<O: ColorOrder> Comet<O> {
+    pub const fn new(color: O, fade: u8) -> Self {
+        Comet { color, fade }
+    }
+}
+
+impl<O: ColorOrder> Effect<O> for Comet<O> {
+    fn step(&mut self, pixels: &mut [O], tick: u32) {
+        if pixels.is_empty() {
+            return;
+        }
+
+        for pixel in pixels.iter_mut() {
+            pixel.fade_to_black_by(self.fade);
+        }
+        pixels[tick as usize % pixels.len()] = self.color;
+    }
+}
+
+/**
+ * Leds that light up at random with random hues and fade out slowly.
+ * On every frame, a new led is lit with a probability of
+ * `chance / 256`, and all of them fade by `fade`.
+ */
+pub struct Twinkle {
+    pub chance: u8,
+    pub fade: u8,
+    rng: Rng,
+}
+
+impl Twinkle {
+    pub const fn new(chance: u8, fade: u8, seed: u16) -> Self {
+        Twinkle {
+            chance,
+            fade,
+            rng: Rng::new(seed),
+        }
+    }
+}
+
+impl<O: ColorOrder> Effect<O> for Twinkle {
+    fn step(&mut self, pixels: &mut [O], _tick: u32) {
+        for pixel in pixels.iter_mut() {
+            pixel.fade_to_black_by(self.fade);
+        }
+
+        if !pixels.is_empty() && self.rng.next_u8() < self.chance {
+            let i = (self.rng.next_u8() as usize * pixels.len()) >> 8;
+            pixels[i] = Hsv8::new(self.rng.next_u8(), 255, 255).to_rainbow();
+        }
+    }
+}
+
+/**
+ * Flames rising from the start of the strip, following the Fire2012
+ * simulation from FastLED. Keeps the heat of each one of the first `N`
+ * leds of the strip. Higher `cooling` values make shorter flames, and
+ * higher `sparking` values make them more active.
+ */
+pub struct Fire<const N: usize> {
+    pub cooling: u8,
+    pub sparking: u8,
+    heat: [u8; N],
+    rng: Rng,
+}
+
+impl<const N: usize> Fire<N> {
+    /// Creates a fire with typical settings: a cooling of 55 and a
+    /// sparking of 120.
+    pub const fn new(seed: u16) -> Self {
+        Fire {
+            cooling: 55,
+            sparking: 120,
+            heat: [0; N],
+            rng: Rng::new(seed),
+        }
+    }
+}
+
+impl<O: ColorOrder, const N: usize> Effect<O> for Fire<N> {
+    fn step(&mut self, pixels: &mut [O], _tick: u32) {
+        let len = N.min(pixels.len());
+        if len == 0 {
+            return;
+        }
+
+        // Every cell cools down a little.
+        let max_cooling = ((self.cooling as usize * 10) / len + 2).min(255) as u8;
+        for heat in &mut self.heat[..len] {
+            *heat = qsub8(*heat, self.rng.below(max_cooling));
+        }
+
+        // The heat drifts up and diffuses.
+        for k in (2..len).rev() {
+            let heat = &mut self.heat;
+            heat[k] = ((heat[k - 1] as u16 + 2 * heat[k - 2] as u16) / 3) as u8;
+        }
+
+        // New sparks are randomly ignited near the bottom.
+        if self.rng.next_u8() < self.sparking {
+            let y = self.rng.below(7.min(len as u8)) as usize;
+            self.heat[y] = qadd8(self.heat[y], self.rng.between(160, 255));
+        }
+
+        for (pixel, &heat) in pixels.iter_mut().zip(&self.heat[..len]) {
+            *pixel = heat_color(heat);
+        }
+    }
+}
+
+/// Maps a temperature into a color of a black body, from black through
+/// red, orange and yellow to white.
+pub fn heat_color<O: ColorOrder>(temperature: u8) -> O {
+    // Scales the temperature into 0..191, with three thirds for each
+    // of the color channels being ramped up.
+    let t192 = scale8_video(temperature, 191);
+    let ramp = (t192 & 0x3F) << 2;
+
+    if t192 & 0x80 != 0 {
+        O::from_rgb(255, 255, ramp)
+    } else if t192 & 0x40 != 0 {
+        O::from_rgb(255, ramp, 0)
+    } else {
+        O::from_rgb(ramp, 0, 0)
+    }
+}
+
+/**
+ * The whole strip glowing in a color whose brightness goes up and down
+ * smoothly. A full breath takes `256 / speed` frames.
+ */
+pub struct Breathing<O> {
+    pub color: O,
+    pub speed: u8,
+}
+
+impl<O: ColorOrder> Breathing<O> {
+    pub const fn new(color: O, speed: u8) -> Self {
+        Breathing { color, speed }
+    }
+}
+
+impl<O: ColorOrder> Effect<O> for Breathing<O> {
+    fn step(&mut self, pixels: &mut [O], tick: u32) {
+        // Starts with the leds off, at the lowest point of the wave.
+        let theta = (tick as u8).wrapping_mul(self.speed).wrapping_add(192);
+        let color = self.color.scale8_video(sin8(theta));
+        pixels.fill(color);
+    }
+}
+
+/**
+ * Fills the strip with a color one led at a time, one led every frame,
+ * and then turns it off again the same way.
+ */
+pub struct ColorWipe<O> {
+    pub color: O,
+    pub background: O,
+}
+
+impl<O: ColorOrder> ColorWipe<O> {
+    pub fn new(color: O) -> Self {
+        ColorWipe {
+            color,
+            background: O::from_rgb(0, 0, 0),
+        }
+    }
+}
+
+impl<O: ColorOrder> Effect<O> for ColorWipe<O> {
+    fn step(&mut self, pixels: &mut [O], tick: u32) {
+        let len = pixels.len();
+        if len == 0 {
+            return;
+        }
+
+        // The wipe with the color and the one with the background take
+        // one frame more than leds each, for showing the full strip.
+        let position = tick as usize % (2 * (len + 1));
+        let (lit, color, rest) = if position <= len {
+            (position, self.color, self.background)
+        } else {
+            (position - len - 1, self.background, self.color)
+        };
+
+        pixels[..lit].fill(color);
+        pixels[lit..].fill(rest);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::RGB;
+
+    fn run<E: Effect<RGB>>(effect: &mut E, leds: usize, steps: u32) -> Vec<(u8, u8, u8)> {
+        let mut pixels = vec![RGB::from_rgb(0, 0, 0); leds];
+        for tick in 0..steps {
+            effect.step(&mut pixels, tick);
+        }
+        pixels.iter().map(|color| color.to_rgb()).collect()
+    }
+
+    #[test]
+    fn rng() {
+        let mut rng = Rng::new(0);
+        let values: Vec<u8> = (0..8).map(|_| rng.next_u8()).collect();
+        assert_eq!(values, [79, 162, 44, 99, 31, 19, 64, 40]);
+    }
+
+    #[test]
+    fn rng_between() {
+        let mut rng = Rng::new(7);
+        for min in 0..=255 {
+            for max in 0..=255 {
+                let value = rng.between(min, max);
+                if min == max {
+                    assert_eq!(value, min);
+                } else {
+                    assert!((min.min(max)..min.max(max)).contains(&value));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rainbow_cycle() {
+        let pixels = run(&mut RainbowCycle::new(32, 3), 8, 10);
+        assert_eq!(
+            pixels,
+            [
+                (183, 72, 0),
+                (171, 157, 0),
+                (28, 242, 0),
+                (0, 183, 72),
+                (0, 28, 228),
+                (72, 0, 183),
+                (157, 0, 99),
+                (242, 0, 13)
+            ]
+        );
+    }
+
+    #[test]
+    fn theater_chase() {
+        let pixels = run(&mut TheaterChase::new(RGB::from_rgb(255, 0, 0), 3), 7, 5);
+        let (on, off) = ((255, 0, 0), (0, 0, 0));
+        assert_eq!(pixels, [off, on, off, off, on, off, off]);
+    }
+
+    #[test]
+    fn comet() {
+        let pixels = run(&mut Comet::new(RGB::from_rgb(255, 128, 0), 64), 6, 9);
+        assert_eq!(
+            pixels,
+            [
+                (143, 72, 0),
+                (191, 96, 0),
+                (255, 128, 0),
+                (60, 30, 0),
+                (80, 40, 0),
+                (107, 54, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn twinkle() {
+        let pixels = run(&mut Twinkle::new(128, 32, 1234), 8, 20);
+        assert_eq!(
+            pixels,
+            [
+                (11, 0, 117),
+                (10, 10, 0),
+                (0, 7, 9),
+                (0, 0, 0),
+                (30, 4, 0),
+                (13, 0, 242),
+                (36, 23, 0),
+                (0, 0, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn fire() {
+        let pixels = run(&mut Fire::<10>::new(42), 10, 30);
+        assert_eq!(
+            pixels,
+            [
+                (0, 0, 0),
+                (192, 0, 0),
+                (64, 0, 0),
+                (156, 0, 0),
+                (108, 0, 0),
+                (255, 100, 0),
+                (255, 255, 44),
+                (180, 0, 0),
+                (255, 44, 0),
+                (255, 36, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn breathing() {
+        let pixels = run(&mut Breathing::new(RGB::from_rgb(0, 200, 100), 8), 2, 21);
+        assert_eq!(pixels, [(0, 171, 86); 2]);
+
+        // The first frame is at the lowest point of the wave.
+        let pixels = run(&mut Breathing::new(RGB::from_rgb(0, 200, 100), 8), 2, 1);
+        assert_eq!(pixels, [(0, 1, 1); 2]);
+    }
+
+    #[test]
+    fn color_wipe() {
+        let pixels = run(&mut ColorWipe::new(RGB::from_rgb(0, 0, 255)), 4, 8);
+        assert_eq!(pixels, [(0, 0, 0), (0, 0, 0), (0, 0, 255), (0, 0, 255)]);
+    }
+
+    #[test]
+    fn empty_strip() {
+        assert!(run(&mut Comet::new(RGB::from_rgb(255, 0, 0), 64), 0, 3).is_empty());
+        assert!(run(&mut Twinkle::new(255, 32, 1), 0, 3).is_empty());
+        assert!(run(&mut Fire::<10>::new(1), 0, 3).is_empty());
+        assert!(run(&mut ColorWipe::new(RGB::from_rgb(255, 0, 0)), 0, 3).is_empty());
+    }
+}
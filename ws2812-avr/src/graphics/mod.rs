@@ -0,0 +1,394 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Drawing primitives for led matrices: pixels, lines, rectangles,
+ * circles, sprites and text. Coordinates are signed, and everything
+ * drawn out of the matrix is clipped, so shapes and text can be moved
+ * partially out of it, like when scrolling.
+ *
+ * With the `embedded-graphics` feature enabled, matrices also implement
+ * the `DrawTarget` trait, so they can be drawn on with any of the
+ * crates of the embedded-graphics ecosystem. */
+pub mod font;
+
+use crate::color::ColorOrder;
+use crate::matrix::{Layout, Matrix};
+use crate::util::progmem::ProgMem;
+use core::mem::size_of;
+use font::Font;
+
+/**
+ * An image of `W` by `H` leds, meant to be stored in the program
+ * memory and drawn on a matrix with [Matrix::blit]:
+ *
+ * ```no_run
+ * progmem! {
+ *     static HEART: Sprite<GRB, 8, 8> = Sprite::new([...]);
+ * }
+ * ```
+ */
+#[repr(transparent)]
+#[derive(Clone, Copy)]
+pub struct Sprite<O, const W: usize, const H: usize> {
+    pixels: [[O; W]; H],
+}
+
+impl<O: Copy, const W: usize, const H: usize> Sprite<O, W, H> {
+    /// Creates a sprite from its rows of pixels.
+    pub const fn new(pixels: [[O; W]; H]) -> Self {
+        Sprite { pixels }
+    }
+
+    /// Returns the color of the pixel at (`x`, `y`).
+    pub fn pixel(&self, x: usize, y: usize) -> O {
+        self.pixels[y][x]
+    }
+}
+
+impl<'a, O: ColorOrder, const W: usize, const H: usize, L: Layout> Matrix<'a, O, W, H, L> {
+    /// Sets the color of the led at (`x`, `y`), if it is in the matrix.
+    pub fn draw_pixel(&mut self, x: i16, y: i16, color: O) {
+        if x >= 0 && y >= 0 {
+            self.set(x as usize, y as usize, color);
+        }
+    }
+
+    /// Draws a line from (`x0`, `y0`) to (`x1`, `y1`), both included.
+    pub fn draw_line(&mut self, x0: i16, y0: i16, x1: i16, y1: i16, color: O) {
+        // Bresenham's algorithm, for all the octants.
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+
+        loop {
+            self.draw_pixel(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Draws the outline of a rectangle of `w` by `h` leds, with its
+    /// top left corner at (`x`, `y`).
+    pub fn draw_rect(&mut self, x: i16, y: i16, w: i16, h: i16, color: O) {
+        if w <= 0 || h <= 0 {
+            return;
+        }
+
+        let (x1, y1) = (x + w - 1, y + h - 1);
+        self.draw_line(x, y, x1, y, color);
+        self.draw_line(x, y1, x1, y1, color);
+        self.draw_line(x, y, x, y1, color);
+        self.draw_line(x1, y, x1, y1, color);
+    }
+
+    /// Draws a filled rectangle of `w` by `h` leds, with its top left
+    /// corner at (`x`, `y`).
+    pub fn fill_rect(&mut self, x: i16, y: i16, w: i16, h: i16, color: O) {
+        for row in y..y + h {
+            for col in x..x + w {
+                self.draw_pixel(col, row, color);
+            }
+        }
+    }
+
+    /// Draws the outline of a circle of radius `r` centered at
+    /// (`cx`, `cy`).
+    pub fn draw_circle(&mut self, cx: i16, cy: i16, r: i16, color: O) {
+        circle_octants(r, |x, y| {
+            for (px, py) in [
+                (x, y),
+                (y, x),
+                (-x, y),
+                (-y, x),
+                (x, -y),
+                (y, -x),
+                (-x, -y),
+                (-y, -x),
+            ] {
+                self.draw_pixel(cx + px, cy + py, color);
+            }
+        });
+    }
+
+    /// Draws a filled circle of radius `r` centered at (`cx`, `cy`).
+    pub fn fill_circle(&mut self, cx: i16, cy: i16, r: i16, color: O) {
+        circle_octants(r, |x, y| {
+            for (dx, dy) in [(x, y), (y, x), (x, -y), (y, -x)] {
+                for px in cx - dx..=cx + dx {
+                    self.draw_pixel(px, cy + dy, color);
+                }
+            }
+        });
+    }
+
+    /// Draws a sprite stored in the program memory, with its top left
+    /// corner at (`x`, `y`).
+    pub fn blit<const SW: usize, const SH: usize>(
+        &mut self,
+        sprite: &ProgMem<Sprite<O, SW, SH>>,
+        x: i16,
+        y: i16,
+    ) {
+        for row in 0..SH {
+            for col in 0..SW {
+                // SAFETY: The pixels of the sprite are stored row by row,
+                // and the offset lies within them.
+                let color: O = unsafe { sprite.read_at((row * SW + col) * size_of::<O>()) };
+                self.draw_pixel(x + col as i16, y + row as i16, color);
+            }
+        }
+    }
+
+    /// Draws a character with its top left corner at (`x`, `y`). Only
+    /// the leds of the glyph are set, leaving the background untouched.
+    pub fn draw_char<const COLS: usize>(
+        &mut self,
+        c: char,
+        x: i16,
+        y: i16,
+        font: &Font<COLS>,
+        color: O,
+    ) {
+        for col in 0..COLS {
+            let bits = font.column(c, col);
+            for row in 0..font.height() {
+                if bits & (1 << row) != 0 {
+                    self.draw_pixel(x + col as i16, y + row as i16, color);
+                }
+            }
+        }
+    }
+
+    /**
+     * Draws a line of text with its top left corner at (`x`, `y`),
+     * leaving one column of spacing between characters. Returns the
+     * horizontal position right after the text, so that scrolling text
+     * can be drawn by decreasing `x` on every frame until the returned
+     * position gets below zero.
+     */
+    pub fn draw_text<const COLS: usize>(
+        &mut self,
+        text: &str,
+        x: i16,
+        y: i16,
+        font: &Font<COLS>,
+        color: O,
+    ) -> i16 {
+        let mut x = x;
+        for c in text.chars() {
+            // Characters entirely out of the matrix are skipped.
+            if x < W as i16 && x + COLS as i16 > 0 {
+                self.draw_char(c, x, y, font, color);
+            }
+            x += COLS as i16 + 1;
+        }
+
+        x
+    }
+}
+
+/// Calls `f` with the points of the first octant of a circle of
+/// radius `r`, following the midpoint circle algorithm.
+fn circle_octants(r: i16, mut f: impl FnMut(i16, i16)) {
+    if r < 0 {
+        return;
+    }
+
+    let (mut x, mut y) = (r, 0);
+    let mut err = 1 - r;
+    while x >= y {
+        f(x, y);
+        y += 1;
+        if err < 0 {
+            err += 2 * y + 1;
+        } else {
+            x -= 1;
+            err += 2 * (y - x) + 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::font::FONT_3X5;
+    use super::*;
+    use crate::color::RGB;
+    use crate::matrix::GridLayout;
+
+    const ON: RGB = RGB::from_rgb(255, 0, 0);
+
+    crate::progmem! {
+        static ARROW: Sprite<RGB, 3, 2> = Sprite::new([
+            [RGB::from_rgb(0, 0, 0), RGB::from_rgb(1, 0, 0), RGB::from_rgb(0, 0, 0)],
+            [RGB::from_rgb(2, 0, 0), RGB::from_rgb(3, 0, 0), RGB::from_rgb(4, 0, 0)],
+        ]);
+    }
+
+    /// Draws on a `W` by `H` matrix, returning its rows with the leds
+    /// that are lit as `#`.
+    fn draw<const W: usize, const H: usize>(
+        f: impl FnOnce(&mut Matrix<RGB, W, H, GridLayout>),
+    ) -> Vec<String> {
+        let mut pixels = vec![RGB::default(); W * H];
+        f(&mut Matrix::new(&mut pixels, GridLayout::new()));
+        pixels
+            .chunks(W)
+            .map(|row| {
+                row.iter()
+                    .map(|pixel| {
+                        if pixel.to_rgb() == (0, 0, 0) {
+                            '.'
+                        } else {
+                            '#'
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lines() {
+        assert_eq!(
+            draw::<5, 3>(|m| m.draw_line(0, 0, 4, 2, ON)),
+            ["#....", ".##..", "...##"]
+        );
+        assert_eq!(
+            draw::<3, 4>(|m| m.draw_line(2, 3, 0, 0, ON)),
+            ["#..", ".#.", ".#.", "..#"]
+        );
+        assert_eq!(
+            draw::<4, 2>(|m| m.draw_line(3, 1, 1, 1, ON)),
+            ["....", ".###"]
+        );
+        assert_eq!(draw::<2, 2>(|m| m.draw_line(1, 0, 1, 0, ON)), [".#", ".."]);
+    }
+
+    #[test]
+    fn circles() {
+        assert_eq!(
+            draw::<5, 5>(|m| m.draw_circle(2, 2, 2, ON)),
+            [".###.", "#...#", "#...#", "#...#", ".###."]
+        );
+        assert_eq!(
+            draw::<5, 5>(|m| m.fill_circle(2, 2, 2, ON)),
+            [".###.", "#####", "#####", "#####", ".###."]
+        );
+        assert_eq!(
+            draw::<3, 3>(|m| m.draw_circle(1, 1, 0, ON)),
+            ["...", ".#.", "..."]
+        );
+        assert_eq!(draw::<3, 3>(|m| m.draw_circle(1, 1, -1, ON)), ["..."; 3]);
+    }
+
+    #[test]
+    fn clipped_rects() {
+        assert_eq!(
+            draw::<4, 3>(|m| m.draw_rect(-1, -1, 3, 3, ON)),
+            [".#..", "##..", "...."]
+        );
+        assert_eq!(
+            draw::<4, 3>(|m| m.draw_rect(2, 1, 4, 4, ON)),
+            ["....", "..##", "..#."]
+        );
+        assert_eq!(
+            draw::<4, 3>(|m| m.fill_rect(-2, 1, 3, 5, ON)),
+            ["....", "#...", "#..."]
+        );
+        assert_eq!(draw::<2, 2>(|m| m.draw_rect(0, 0, 0, 2, ON)), [".."; 2]);
+    }
+
+    #[test]
+    fn blit() {
+        let mut pixels = [RGB::default(); 12];
+        let mut matrix = Matrix::<RGB, 4, 3, _>::new(&mut pixels, GridLayout::new());
+        matrix.blit(&ARROW, 0, 1);
+        matrix.blit(&ARROW, 2, -1);
+
+        let reds = pixels.map(|pixel| pixel.to_rgb().0);
+        assert_eq!(reds, [0, 0, 2, 3, 0, 1, 0, 0, 2, 3, 4, 0]);
+    }
+
+    #[test]
+    fn text() {
+        let mut end = 0;
+        assert_eq!(
+            draw::<7, 5>(|m| end = m.draw_text("Hi", 0, 0, &FONT_3X5, ON)),
+            ["#.#.###", "#.#..#.", "###..#.", "#.#..#.", "#.#.###"]
+        );
+        assert_eq!(end, 8);
+
+        // Scrolled out on the left, and cut at the bottom.
+        assert_eq!(
+            draw::<4, 3>(|m| end = m.draw_text("Hi", -2, 1, &FONT_3X5, ON)),
+            ["....", "#.##", "#..#"]
+        );
+        assert_eq!(end, 6);
+    }
+}
+
+#[cfg(feature = "embedded-graphics")]
+mod embedded_graphics_interop {
+    use crate::color::ColorOrder;
+    use crate::matrix::{Layout, Matrix};
+    use embedded_graphics_core::draw_target::DrawTarget;
+    use embedded_graphics_core::geometry::{OriginDimensions, Size};
+    use embedded_graphics_core::pixelcolor::{Rgb888, RgbColor};
+    use embedded_graphics_core::Pixel;
+
+    impl<'a, O: ColorOrder, const W: usize, const H: usize, L: Layout> OriginDimensions
+        for Matrix<'a, O, W, H, L>
+    {
+        fn size(&self) -> Size {
+            Size::new(W as u32, H as u32)
+        }
+    }
+
+    impl<'a, O: ColorOrder, const W: usize, const H: usize, L: Layout> DrawTarget
+        for Matrix<'a, O, W, H, L>
+    {
+        type Color = Rgb888;
+        type Error = core::convert::Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            for Pixel(point, color) in pixels {
+                if point.x >= 0 && point.y >= 0 {
+                    let color = O::from_rgb(color.r(), color.g(), color.b());
+                    self.set(point.x as usize, point.y as usize, color);
+                }
+            }
+
+            Ok(())
+        }
+    }
+}
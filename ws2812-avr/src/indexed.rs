@@ -0,0 +1,167 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+/*! Frame buffers that store a palette index for each led instead of
+ * its color. They are expanded into colors while being sent, through
+ * [crate::WS2812::write_indexed], taking 3 to 6 times less RAM than a
+ * buffer of colors. */
+
+/**
+ * A frame buffer of palette indices.
+ */
+pub trait IndexedPixels {
+    /// Number of leds of the buffer.
+    fn len(&self) -> usize;
+
+    /// Returns the palette index of the `i`-th led.
+    fn get(&self, i: usize) -> u8;
+
+    /// Sets the palette index of the `i`-th led.
+    fn set(&mut self, i: usize, index: u8);
+
+    /// Returns whether the buffer has no leds.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Sets the palette index of all the leds of the buffer.
+    fn fill(&mut self, index: u8) {
+        for i in 0..self.len() {
+            self.set(i, index);
+        }
+    }
+}
+
+/**
+ * A frame buffer of `N` leds that stores a 4-bit index for each one,
+ * to be used along with a 16 color palette. Takes N / 2 bytes.
+ */
+#[derive(Clone, Copy)]
+pub struct IndexedBuffer4<const N: usize>
+where
+    [(); (N + 1) / 2]:,
+{
+    data: [u8; (N + 1) / 2],
+}
+
+/**
+ * A frame buffer of `N` leds that stores an 8-bit index for each one,
+ * to be used along with a 256 color palette. With a 16 color palette,
+ * the indices wrap around every 16 entries. Takes N bytes.
+ */
+#[derive(Clone, Copy)]
+pub struct IndexedBuffer8<const N: usize> {
+    data: [u8; N],
+}
+
+impl<const N: usize> IndexedBuffer4<N>
+where
+    [(); (N + 1) / 2]:,
+{
+    pub const fn new() -> Self {
+        IndexedBuffer4 {
+            data: [0; (N + 1) / 2],
+        }
+    }
+}
+
+impl<const N: usize> Default for IndexedBuffer4<N>
+where
+    [(); (N + 1) / 2]:,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> IndexedPixels for IndexedBuffer4<N>
+where
+    [(); (N + 1) / 2]:,
+{
+    #[inline(always)]
+    fn len(&self) -> usize {
+        N
+    }
+
+    #[inline(always)]
+    fn get(&self, i: usize) -> u8 {
+        let byte = self.data[i / 2];
+        if i & 1 == 0 {
+            byte & 0x0F
+        } else {
+            byte >> 4
+        }
+    }
+
+    #[inline(always)]
+    fn set(&mut self, i: usize, index: u8) {
+        let byte = &mut self.data[i / 2];
+        if i & 1 == 0 {
+            *byte = (*byte & 0xF0) | (index & 0x0F);
+        } else {
+            *byte = (*byte & 0x0F) | (index << 4);
+        }
+    }
+
+    fn fill(&mut self, index: u8) {
+        let index = index & 0x0F;
+        self.data = [index | (index << 4); (N + 1) / 2];
+    }
+}
+
+impl<const N: usize> IndexedBuffer8<N> {
+    pub const fn new() -> Self {
+        IndexedBuffer8 { data: [0; N] }
+    }
+
+    /// Returns the indices of the buffer.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the indices of the buffer, as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
+
+impl<const N: usize> Default for IndexedBuffer8<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> IndexedPixels for IndexedBuffer8<N> {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        N
+    }
+
+    #[inline(always)]
+    fn get(&self, i: usize) -> u8 {
+        self.data[i]
+    }
+
+    #[inline(always)]
+    fn set(&mut self, i: usize, index: u8) {
+        self.data[i] = index;
+    }
+
+    fn fill(&mut self, index: u8) {
+        self.data = [index; N];
+    }
+}
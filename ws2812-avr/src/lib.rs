@@ -25,106 +25,131 @@ along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
 #![feature(adt_const_params)]
 #![feature(const_trait_impl)]
 #![feature(const_slice_index)]
+#[cfg(feature = "adalight")]
+pub mod adalight;
 mod color;
+mod dither;
+pub mod effects;
+pub mod graphics;
+pub mod indexed;
+pub mod math8;
+pub mod matrix;
+pub mod palette;
 mod ports;
+pub mod power;
+pub mod scheduler;
+pub mod segment;
+pub mod strip;
+pub mod summary;
+#[cfg(feature = "tpm2")]
+pub mod tpm2;
+pub mod transition;
 pub mod util;
 pub use color::*;
-
+pub use math8::ColorMath;
+#[cfg(feature = "raw-pins")]
+pub use ports::pins;
+
+use crate::dither::{scale8_dithered, Dither};
+use crate::indexed::IndexedPixels;
+use crate::palette::Palette;
+use crate::power::PowerModel;
 use crate::util::asm::{asm_block, branch_not_equal, dec, ld_immediate, lsl, skip_if_bit_set};
-use arduino_hal::port::{mode::Output, Pin};
 use avr_hal_generic::avr_device::interrupt::free;
 use core::marker::PhantomData;
 use core::mem::size_of;
-use ports::{StaticPin, StaticPort};
+use ports::{OutputPin, StaticPin, StaticPort};
 use util::time::TimeVal;
-use util::{time, NopBlock, NopGen};
+use util::{delay, time, NopBlock, NopGen};
 
+/**
+The fixed cycle counts of the loop, which live in the ws2812-timings
+crate, are the same on the classic AVR cores and on the AVRxt cores
+of the megaAVR-0 and tinyAVR-0/1/2 families: `sbrs`, `lsl`, `dec`
+and `brne` keep their timings, and the pin is toggled
+with a single-cycle `out` instruction either into PORTx or into
+VPORTx.OUT. Note that PORTx on AVRxt cores is not reachable by `out`
+and an `sts` would take an extra cycle, which is why the VPORTx
+registers are used on those devices.
+ */
 mod consts {
+    #[cfg(feature = "arduino-hal")]
     use arduino_hal::{clock::Clock, DefaultClock};
 
-    /** The number of fixed cycles (non-nop instructions) consumed by
-    the instructions of the loop after enabling the pin until it is
-    disabled, when the value of the bit that is being set is zero. The
-    current value is based on the behavior of this code, which is in
-    charge of enabling the pin during data sending:
-    ```no_run
-    ...
-    pin_high();                      // Pin is enabled just after this instruction, so this cycle is not counted.
-    Ts::S1Nops::gen();               // Generates a variable number of NOP instructions, not counted.
-    skip_if_bit_set!(curbyte, "7");  // sbrs instruction. Takes 2 cycles when bit is set, 1 when not. Therefore, it counts as 1.
-    pin_low();                       // Pin is disabled just after this instruction, so it counts as 1.
-    ...
-    ```
-     */
-    pub const FIXED_CYCLES_T0H: u8 = 2;
-
-    /**
-    The number of fixed cycles (non-nop instructions) consumed by the
-    instructions of the loop after enabling the pin until it is
-    disabled, when the value of the bit that is being set is one. The
-    current value is based on the behavior of this code, which is in
-    charge of enabling the pin during data sending:
-    ```no_run
-    ...
-    pin_high();                      // Pin is enabled just after this instruction, so this cycle is not counted.
-    Ts::S1Nops::gen();               // Generates a variable number of NOP instructions, not counted.
-    skip_if_bit_set!(curbyte, "7");  // sbrs instruction. Takes 2 cycles when bit is set, 1 when not. Therefore, it counts as 2.
-    pin_low();                       // Instruction skipped by previous instruction.
-    lsl!(curbyte);                   // lsl instruction, takes 1 cycle.
-    Ts::S2Nops::gen();               // Generates a variable number of NOP instructions, not counted.
-    pin_low();                       // Pin is disabled just after this instruction, so it counts as 1.
-    ...
-    ```
-    */
-    pub const FIXED_CYCLES_T1H: u8 = 4;
-
-    /**
-    The number of fixed cycles (non-nop instructions) that takes the
-    program to give a full loop through the code in charge of send
-    color signals to the device. The current value for this constant
-    is calculated by counting the fixed instructions from the code in
-    charge of sending this data to the device:
-    ```no_run
-    ...
-    2: pin_high();                   // When the code loops, it ends here putting the pin high again. So it counts as 1 cycle.
-    Ts::S1Nops::gen();               // Generates a variable number of NOP instructions, not counted.
-    skip_if_bit_set!(curbyte, "7");  // This and the next instruction
-                                     // always count as two cycles: if the next instruction is skipped,
-                                     // then the sbrs instruction takes 2 cycles. If it is not skipped,
-                                     // sbrs takes 1 cycle and the next one takes 1 cycle as well.
-    pin_low();
-    lsl!(curbyte);                   // Takes 1 instruction.
-    Ts::S2Nops::gen();               // Generates a variable number of NOP instructions, not counted.
-    pin_low();                       // Takes 1 instruction.
-    Ts::S3Nops::gen();               // Generates a variable number of NOP instructions, not counted.
-    dec!(i);                         // Takes 1 instruction.
-    branch_not_equal!("2b");         // Takes 2 instructions when the loop continues.
-    ...
-    ```
-    */
-    pub const FIXED_CYCLES_TOTAL: u8 = 8;
+    /// The fixed cycles of the loop that sends the data, which are
+    /// shared with the host tools through the ws2812-timings crate.
+    pub use ws2812_timings::{
+        FIXED_CYCLES_BYTE, FIXED_CYCLES_T0H, FIXED_CYCLES_T1H, FIXED_CYCLES_TOTAL,
+    };
 
     /// Holds the clock speed of the CPU
+    #[cfg(feature = "arduino-hal")]
     pub const F_CPU: u32 = DefaultClock::FREQ;
+
+    /// Holds the clock speed of the CPU. These boards run at 16 MHz
+    /// only once the main clock prescaler, which divides it by 6 after
+    /// a reset, is disabled with [crate::disable_clock_prescaler].
+    #[cfg(all(
+        not(feature = "arduino-hal"),
+        any(feature = "arduino-nano-every", feature = "arduino-uno-wifi-rev2")
+    ))]
+    pub const F_CPU: u32 = 16_000_000;
+
+    /// Holds the clock speed of the CPU. When no board is selected,
+    /// it is read from the `AVR_CPU_FREQUENCY_HZ` environment variable
+    /// at compile time.
+    #[cfg(not(any(
+        feature = "arduino-hal",
+        feature = "arduino-nano-every",
+        feature = "arduino-uno-wifi-rev2"
+    )))]
+    pub const F_CPU: u32 = crate::util::const_str::parse_u32(env!(
+        "AVR_CPU_FREQUENCY_HZ",
+        "No board feature is enabled, so the CPU frequency must be set through the AVR_CPU_FREQUENCY_HZ environment variable"
+    ));
     pub(crate) const NANOS_IN_SECOND: u64 = 1000000000;
 }
 
-macro_rules! diff_clamp_zero {
-    ($a:expr, $b: expr) => {
-	if ($a) >= ($b) {
-	    ($a) - ($b)
-	} else {
-	    0
-	}
-    };
-
-    ($a:expr, $b: expr, $($c:expr),*) => {
-	if ($a) >= ($b) {
-	    diff_clamp_zero!(($a) - ($b), $($c),*)
-	} else {
-	    0
-	}
-    };
+/**
+ * Disables the prescaler of the main clock of the megaAVR-0 and
+ * tinyAVR-0/1/2 families, which divides the clock by 6 after a reset,
+ * so the CPU runs at the frequency of the oscillator selected by the
+ * fuses. It must be called at startup, before sending any data, for
+ * the timings to be calculated with the right CPU frequency, like the
+ * 16 MHz of the Arduino Nano Every and Uno WiFi Rev2.
+ *
+ * CLKCTRL.MCLKCTRLB is protected by the Configuration Change
+ * Protection, so it is cleared right after writing the I/O register
+ * signature to CPU.CCP, while interrupts are ignored.
+ */
+#[cfg(any(
+    feature = "atmega4808",
+    feature = "atmega4809",
+    feature = "attiny804",
+    feature = "attiny1614",
+    feature = "attiny1616",
+    feature = "attiny1624",
+    feature = "attiny1626",
+    feature = "attiny3216",
+    feature = "attiny3224",
+    feature = "attiny3226"
+))]
+#[inline(always)]
+pub fn disable_clock_prescaler() {
+    const CPU_CCP: u8 = 0x34;
+    const CLKCTRL_MCLKCTRLB: u16 = 0x61;
+    const CCP_IOREG: u8 = 0xD8;
+
+    unsafe {
+        core::arch::asm!(
+            "out {ccp}, {signature}",
+            "sts {mclkctrlb}, {zero}",
+            ccp = const CPU_CCP,
+            mclkctrlb = const CLKCTRL_MCLKCTRLB,
+            signature = in(reg) CCP_IOREG,
+            zero = in(reg) 0u8,
+        );
+    }
 }
 
 // Seems that Rust and LLVM will remove duplicated functions with the
@@ -193,6 +218,14 @@ pub trait CalculatedTimings {
     /// of enabling and disabling the pin.
     const TOTAL_CYCLES: u8;
 
+    /// Total number of CPU cycles that the reset time takes.
+    const RST_CYCLES: u32;
+
+    /// Number of CPU cycles that sending a bit actually takes, which
+    /// may be longer than [CalculatedTimings::TOTAL_CYCLES] when the
+    /// fixed cycles of the loop do not fit into it.
+    const BIT_CYCLES: u32;
+
     /// Number of NOP cycles at stage 1.
     const S1_NOPS: u8;
     /// Number of NOP cycles at stage 2.
@@ -201,20 +234,84 @@ pub trait CalculatedTimings {
     const S3_NOPS: u8;
 }
 
+/// Runs the calculations shared with the host tools for the timings.
+pub(crate) const fn calculate<Ts: Timings>() -> ws2812_timings::Calculated {
+    ws2812_timings::calculate(
+        consts::F_CPU,
+        Ts::T0h::NANOS,
+        Ts::T1h::NANOS,
+        Ts::Cycle::NANOS,
+        Ts::Rst::NANOS,
+    )
+}
+
 impl<Ts: Timings> CalculatedTimings for Ts {
-    const T0H_CYCLES: u8 = (consts::F_CPU as u64 * Ts::T0h::NANOS / consts::NANOS_IN_SECOND) as u8;
-    const T1H_CYCLES: u8 = (consts::F_CPU as u64 * Ts::T1h::NANOS / consts::NANOS_IN_SECOND) as u8;
-    const TOTAL_CYCLES: u8 =
-        (consts::F_CPU as u64 * Ts::Cycle::NANOS / consts::NANOS_IN_SECOND) as u8;
-
-    const S1_NOPS: u8 = diff_clamp_zero!(Self::T0H_CYCLES, consts::FIXED_CYCLES_T0H);
-    const S2_NOPS: u8 = diff_clamp_zero!(Self::T1H_CYCLES, consts::FIXED_CYCLES_T1H, Self::S1_NOPS);
-    const S3_NOPS: u8 = diff_clamp_zero!(
-        Self::TOTAL_CYCLES,
-        consts::FIXED_CYCLES_TOTAL,
-        Self::S1_NOPS,
-        Self::S2_NOPS
-    );
+    const T0H_CYCLES: u8 = calculate::<Ts>().t0h_cycles;
+    const T1H_CYCLES: u8 = calculate::<Ts>().t1h_cycles;
+    const TOTAL_CYCLES: u8 = calculate::<Ts>().total_cycles;
+    const RST_CYCLES: u32 = calculate::<Ts>().rst_cycles;
+
+    const S1_NOPS: u8 = calculate::<Ts>().s1_nops;
+    const S2_NOPS: u8 = calculate::<Ts>().s2_nops;
+    const S3_NOPS: u8 = calculate::<Ts>().s3_nops;
+
+    const BIT_CYCLES: u32 = calculate::<Ts>().bit_cycles();
+}
+
+/**
+ * Estimated number of CPU cycles that it takes to send the colors of
+ * `leds` leds with the given timings and color order, without the
+ * reset pause. The gap between bytes is the one of the loop generated
+ * for [WS2812::write] (see `FIXED_CYCLES_BYTE` in `ws2812-timings`),
+ * so the estimate holds for it, as long as the compiler generates the
+ * same loop. The paths built on [WS2812::write_iter] compute each
+ * pixel while sending, so they take longer by an amount that depends
+ * on their iterator, and the estimate is only a lower bound for them.
+ */
+pub const fn transmit_cycles_estimate<Ts: Timings, Order>(leds: usize) -> u64 {
+    let bytes = (leds * size_of::<Order>()) as u64;
+    ws2812_timings::transmit_cycles_estimate(Ts::BIT_CYCLES, bytes)
+}
+
+/**
+ * Estimated nanoseconds that it takes to send the colors of `leds` leds
+ * with the given timings and color order, without the reset pause. See
+ * [transmit_cycles_estimate] for the write paths it holds for.
+ */
+pub const fn transmit_nanos_estimate<Ts: Timings, Order>(leds: usize) -> u64 {
+    ws2812_timings::cycles_to_nanos(consts::F_CPU, transmit_cycles_estimate::<Ts, Order>(leds))
+}
+
+/**
+ * Estimated nanoseconds that it takes to send a frame of `leds` leds
+ * with the given timings and color order, including the reset pause
+ * after it, which is the shortest time between the starts of two
+ * frames. See [transmit_cycles_estimate] for the write paths it holds
+ * for.
+ */
+pub const fn frame_nanos_estimate<Ts: Timings, Order>(leds: usize) -> u64 {
+    transmit_nanos_estimate::<Ts, Order>(leds) + Ts::Rst::NANOS
+}
+
+/**
+ * Estimated highest number of frames of `leds` leds per second that
+ * can be sent with the given timings and color order, which can be
+ * checked against the frame rate required by a project. It is only an
+ * upper bound for the paths built on [WS2812::write_iter], so a frame
+ * rate checked with it should leave some margin when they are used.
+ * It saturates to `u32::MAX` when a frame takes no time at all:
+ *
+ * ```no_run
+ * const _: () = assert!(ws2812_avr::max_fps_estimate::<DefaultTimings, GRB>(LED_COUNT) >= 60);
+ * ```
+ */
+pub const fn max_fps_estimate<Ts: Timings, Order>(leds: usize) -> u32 {
+    let nanos = frame_nanos_estimate::<Ts, Order>(leds);
+    if nanos == 0 {
+        return u32::MAX;
+    }
+
+    (consts::NANOS_IN_SECOND / nanos) as u32
 }
 
 impl<Ts: Timings> TypedTimings for Ts
@@ -231,9 +328,9 @@ where
 /**
  * Represents a driver for WS2812 leds.
  */
-#[repr(transparent)]
 pub struct WS2812<P, Ts, Order> {
-    _pin: Pin<Output, P>,
+    _pin: OutputPin<P>,
+    dither: Dither,
     _ts: PhantomData<Ts>,
     _order: PhantomData<Order>,
 }
@@ -244,17 +341,19 @@ pub struct WS2812<P, Ts, Order> {
 type WS2812Default<Pin, Order> = WS2812<Pin, DefaultTimings, Order>;
 
 impl WS2812<!, !, !> {
-    pub fn new<P: StaticPin, Order>(pin: Pin<Output, P>) -> WS2812Default<P, Order> {
+    pub fn new<P: StaticPin, Order>(pin: OutputPin<P>) -> WS2812Default<P, Order> {
         WS2812 {
             _pin: pin,
+            dither: Dither::new(),
             _ts: PhantomData,
             _order: PhantomData,
         }
     }
 
-    pub fn new_with_timings<Ts, P: StaticPin, Order>(pin: Pin<Output, P>) -> WS2812<P, Ts, Order> {
+    pub fn new_with_timings<Ts, P: StaticPin, Order>(pin: OutputPin<P>) -> WS2812<P, Ts, Order> {
         WS2812 {
             _pin: pin,
+            dither: Dither::new(),
             _ts: PhantomData,
             _order: PhantomData,
         }
@@ -268,9 +367,7 @@ impl<Pin: StaticPin, Ts: TypedTimings, Order: ColorOrder> WS2812<Pin, Ts, Order>
             // - Pin ownership is ensured by holding it into this structure.
             // - Previous call to free ensures a interrupt-free context.
             unsafe {
-                let port_value: u8 = Pin::Port::read();
-                let maskhi = port_value | (1 << Pin::PIN_BIT_INDEX);
-                let masklo = port_value & !(1 << Pin::PIN_BIT_INDEX);
+                let (maskhi, masklo) = Self::port_masks();
                 ws2812_write::<Pin::Port, Ts>(
                     data.as_ptr() as *const u8,
                     data.len() * size_of::<Order>(),
@@ -280,7 +377,126 @@ impl<Pin: StaticPin, Ts: TypedTimings, Order: ColorOrder> WS2812<Pin, Ts, Order>
             }
         });
 
-        arduino_hal::delay_us(Ts::Rst::MICROS as u32);
+        delay::delay_cycles(Ts::RST_CYCLES);
+    }
+
+    /**
+     * Writes the colors yielded by the given iterator. Each color is
+     * sent as soon as it is yielded, so the iterator must be able to
+     * produce the next color in a few microseconds, otherwise the
+     * device may take the pause as a reset. Interrupts are disabled
+     * while the iterator is consumed.
+     */
+    pub fn write_iter<I: IntoIterator<Item = Order>>(&mut self, data: I) {
+        free(|_cs| {
+            // SAFETY:
+            // - Pin ownership is ensured by holding it into this structure.
+            // - Previous call to free ensures a interrupt-free context.
+            unsafe {
+                let (maskhi, masklo) = Self::port_masks();
+                for color in data {
+                    ws2812_write::<Pin::Port, Ts>(
+                        &color as *const Order as *const u8,
+                        size_of::<Order>(),
+                        maskhi,
+                        masklo,
+                    );
+                }
+            }
+        });
+
+        delay::delay_cycles(Ts::RST_CYCLES);
+    }
+
+    /**
+     * Enables or disables temporal dithering on the frames written
+     * with [WS2812::write_scaled] and [WS2812::write_limited]. It is
+     * disabled by default. Dithering smooths fades at low brightness,
+     * as long as frames are written often enough, at least around 100
+     * times per second, for the alternation not to be seen as flicker.
+     */
+    pub fn set_dithering(&mut self, enabled: bool) {
+        self.dither.set_enabled(enabled);
+    }
+
+    /**
+     * Writes a frame, scaling the value of every channel by
+     * `brightness / 256` while it is being sent, so the buffer is
+     * left untouched. If dithering is enabled, the values are rounded
+     * differently on each frame.
+     */
+    pub fn write_scaled(&mut self, data: &[Order], brightness: u8) {
+        let (mut offset, toggle) = self.dither.next_frame();
+        self.write_iter(data.iter().map(move |color| {
+            let (r, g, b) = color.to_rgb();
+            let color = Order::from_rgb(
+                scale8_dithered(r, brightness, offset),
+                scale8_dithered(g, brightness, offset),
+                scale8_dithered(b, brightness, offset),
+            );
+            offset = toggle - offset;
+            color
+        }));
+    }
+
+    /**
+     * Writes a frame, dimming it if needed so that the estimated
+     * current drawn by the leds, according to the given power model,
+     * stays under `budget_ma` milliamps. Returns the brightness the
+     * frame was sent with, where 255 means that it was not dimmed.
+     */
+    pub fn write_limited(&mut self, data: &[Order], model: &PowerModel, budget_ma: u32) -> u8 {
+        let brightness = model.max_brightness(data, budget_ma);
+        if brightness == 255 {
+            self.write(data);
+        } else {
+            self.write_scaled(data, brightness);
+        }
+
+        brightness
+    }
+
+    /**
+     * Writes a frame of palette indices, looking up the color of each
+     * led into the palette right before sending it, in the pause
+     * between consecutive leds. The palette lookup is a plain entry
+     * read, without blending, so the indices should be lower than the
+     * number of entries of the palette.
+     */
+    pub fn write_indexed<B: IndexedPixels, P: Palette<Order>>(&mut self, indices: &B, palette: &P) {
+        self.write_iter((0..indices.len()).map(|i| palette.entry(indices.get(i))));
+    }
+
+    /// Returns the values the port must be set to for putting the pin
+    /// high and low, respectively, without altering the rest of pins.
+    #[inline(always)]
+    unsafe fn port_masks() -> (u8, u8) {
+        let port_value: u8 = Pin::Port::read();
+        (
+            port_value | (1 << Pin::PIN_BIT_INDEX),
+            port_value & !(1 << Pin::PIN_BIT_INDEX),
+        )
+    }
+}
+
+#[cfg(feature = "smart-leds")]
+impl<Pin: StaticPin, Ts: TypedTimings, Order: ColorOrder> smart_leds_trait::SmartLedsWrite
+    for WS2812<Pin, Ts, Order>
+{
+    type Error = core::convert::Infallible;
+    type Color = smart_leds_trait::RGB8;
+
+    fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        self.write_iter(iterator.into_iter().map(|color| {
+            let color: smart_leds_trait::RGB8 = color.into();
+            Order::from_rgb(color.r, color.g, color.b)
+        }));
+
+        Ok(())
     }
 }
 
@@ -0,0 +1,461 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+/*! Fast 8-bit fixed-point math for working with colors, modeled after
+ * the lib8tion library from FastLED. Values are treated as fractions
+ * of 256, so that a scale of 128 means one half.
+ *
+ * The scaling functions use the hardware multiplier through inline
+ * assembly on the devices that have one. The `_const` variants are
+ * portable versions of them that can be used in const contexts and
+ * that produce the exact same results. */
+use crate::color::ColorOrder;
+#[cfg(all(target_arch = "avr", feature = "hw-mul"))]
+use core::arch::asm;
+
+/// Adds two values, saturating at 255.
+#[inline(always)]
+pub const fn qadd8(i: u8, j: u8) -> u8 {
+    i.saturating_add(j)
+}
+
+/// Subtracts `j` from `i`, saturating at 0.
+#[inline(always)]
+pub const fn qsub8(i: u8, j: u8) -> u8 {
+    i.saturating_sub(j)
+}
+
+/// Scales `i` by `scale / 256`. A scale of 255 leaves the value
+/// untouched.
+#[inline(always)]
+pub const fn scale8_const(i: u8, scale: u8) -> u8 {
+    ((i as u16 * (1 + scale as u16)) >> 8) as u8
+}
+
+/// Scales `i` by `scale / 256`, guaranteeing that the result is only
+/// zero when either `i` or `scale` are zero, so that a lit channel is
+/// never turned off by dimming it.
+#[inline(always)]
+pub const fn scale8_video_const(i: u8, scale: u8) -> u8 {
+    let scaled = ((i as u16 * scale as u16) >> 8) as u8;
+    if i != 0 && scale != 0 {
+        scaled + 1
+    } else {
+        scaled
+    }
+}
+
+/// Scales `i` by `scale / 256`. See [scale8_const].
+#[inline(always)]
+pub fn scale8(i: u8, scale: u8) -> u8 {
+    #[cfg(all(target_arch = "avr", feature = "hw-mul"))]
+    {
+        let mut i = i;
+        // SAFETY: r0 and r1 are clobbered by mul, so both are restored
+        // before leaving the block: r0 from its saved copy, and r1 back
+        // to zero.
+        unsafe {
+            asm!(
+                "mov {saved}, r0",
+                "mul {i}, {scale}",
+                "add r0, {i}",
+                "ldi {i}, 0",
+                "adc {i}, r1",
+                "clr r1",
+                "mov r0, {saved}",
+                i = inout(reg_upper) i,
+                scale = in(reg) scale,
+                saved = out(reg) _,
+            );
+        }
+        i
+    }
+
+    #[cfg(not(all(target_arch = "avr", feature = "hw-mul")))]
+    {
+        scale8_const(i, scale)
+    }
+}
+
+/// Scales `i` by `scale / 256`, never turning a lit channel off. See
+/// [scale8_video_const].
+#[inline(always)]
+pub fn scale8_video(i: u8, scale: u8) -> u8 {
+    #[cfg(all(target_arch = "avr", feature = "hw-mul"))]
+    {
+        let mut j: u8 = 0;
+        // SAFETY: r0 and r1 are clobbered by mul, so both are restored
+        // before leaving the block: r0 from its saved copy, and r1 back
+        // to zero.
+        unsafe {
+            asm!(
+                "tst {i}",
+                "breq 2f",
+                "mov {saved}, r0",
+                "mul {i}, {scale}",
+                "mov {j}, r1",
+                "clr r1",
+                "mov r0, {saved}",
+                "cpse {scale}, r1",
+                "subi {j}, 0xFF",
+                "2:",
+                j = inout(reg_upper) j,
+                i = in(reg) i,
+                scale = in(reg) scale,
+                saved = out(reg) _,
+            );
+        }
+        j
+    }
+
+    #[cfg(not(all(target_arch = "avr", feature = "hw-mul")))]
+    {
+        scale8_video_const(i, scale)
+    }
+}
+
+/// Blends `a` into `b`, where an `amount` of 0 gives `a` and 255 gives `b`.
+#[inline(always)]
+pub const fn blend8(a: u8, b: u8, amount: u8) -> u8 {
+    // The weights always add up to 257, so the sum fits in 16 bits.
+    ((a as u16 * (256 - amount as u16) + b as u16 * (1 + amount as u16)) >> 8) as u8
+}
+
+/// Linearly interpolates between `a` and `b`, by `frac / 256`.
+#[inline(always)]
+pub fn lerp8by8(a: u8, b: u8, frac: u8) -> u8 {
+    if b > a {
+        a + scale8(b - a, frac)
+    } else {
+        a - scale8(a - b, frac)
+    }
+}
+
+/// Values of the first quarter of the sine wave, stored as pairs of
+/// base and slope for each of its four sections.
+const SIN8_TABLE: [u8; 8] = [0, 49, 49, 41, 90, 27, 117, 10];
+
+/**
+ * Fast approximation of the sine function, where `theta` covers a
+ * full turn in the range 0-255 and the result goes from 1 to 255,
+ * with 128 being zero.
+ */
+pub const fn sin8(theta: u8) -> u8 {
+    let mut offset = theta;
+    if theta & 0x40 != 0 {
+        offset = 255 - offset;
+    }
+    offset &= 0x3F;
+
+    let mut secoffset = offset & 0x0F;
+    if theta & 0x40 != 0 {
+        secoffset += 1;
+    }
+
+    let section = (offset >> 4) as usize;
+    let b = SIN8_TABLE[section * 2];
+    let m16 = SIN8_TABLE[section * 2 + 1];
+    let mx = ((m16 as u16 * secoffset as u16) >> 4) as u8;
+
+    let mut y = (mx + b) as i8;
+    if theta & 0x80 != 0 {
+        y = -y;
+    }
+
+    (y as u8).wrapping_add(128)
+}
+
+/// Fast approximation of the cosine function. See [sin8].
+pub const fn cos8(theta: u8) -> u8 {
+    sin8(theta.wrapping_add(64))
+}
+
+/// Quadratic ease-in/ease-out function, mapping 0-255 into 0-255
+/// with a slow start and a slow end.
+pub fn ease8(i: u8) -> u8 {
+    let j = if i & 0x80 != 0 { 255 - i } else { i };
+    let jj2 = scale8(j, j) << 1;
+    if i & 0x80 != 0 {
+        255 - jj2
+    } else {
+        jj2
+    }
+}
+
+/**
+ * Fixed-point math operations on colors, applied independently to each
+ * of their channels.
+ */
+pub trait ColorMath: ColorOrder {
+    /// Scales each channel by `scale / 256`.
+    fn scale8(self, scale: u8) -> Self;
+
+    /// Scales each channel by `scale / 256` without turning off any lit channel.
+    fn scale8_video(self, scale: u8) -> Self;
+
+    /// Scales each channel by `scale / 256`, in place.
+    fn nscale8(&mut self, scale: u8);
+
+    /// Dims the color towards black by `amount / 256`.
+    fn fade_to_black_by(&mut self, amount: u8);
+
+    /// Adds two colors, saturating each channel at 255.
+    fn saturating_add(self, other: Self) -> Self;
+
+    /// Subtracts a color from another one, saturating each channel at 0.
+    fn saturating_sub(self, other: Self) -> Self;
+
+    /// Blends this color into another one, where an `amount` of 0
+    /// gives this color and 255 gives the other one.
+    fn blend(self, other: Self, amount: u8) -> Self;
+
+    /// Linearly interpolates between this color and another one, by `frac / 256`.
+    fn lerp(self, other: Self, frac: u8) -> Self;
+}
+
+impl<O: ColorOrder> ColorMath for O {
+    #[inline(always)]
+    fn scale8(self, scale: u8) -> Self {
+        let (r, g, b) = self.to_rgb();
+        O::from_rgb(scale8(r, scale), scale8(g, scale), scale8(b, scale))
+    }
+
+    #[inline(always)]
+    fn scale8_video(self, scale: u8) -> Self {
+        let (r, g, b) = self.to_rgb();
+        O::from_rgb(
+            scale8_video(r, scale),
+            scale8_video(g, scale),
+            scale8_video(b, scale),
+        )
+    }
+
+    #[inline(always)]
+    fn nscale8(&mut self, scale: u8) {
+        *self = self.scale8(scale);
+    }
+
+    #[inline(always)]
+    fn fade_to_black_by(&mut self, amount: u8) {
+        self.nscale8(255 - amount);
+    }
+
+    #[inline(always)]
+    fn saturating_add(self, other: Self) -> Self {
+        let (r1, g1, b1) = self.to_rgb();
+        let (r2, g2, b2) = other.to_rgb();
+        O::from_rgb(qadd8(r1, r2), qadd8(g1, g2), qadd8(b1, b2))
+    }
+
+    #[inline(always)]
+    fn saturating_sub(self, other: Self) -> Self {
+        let (r1, g1, b1) = self.to_rgb();
+        let (r2, g2, b2) = other.to_rgb();
+        O::from_rgb(qsub8(r1, r2), qsub8(g1, g2), qsub8(b1, b2))
+    }
+
+    #[inline(always)]
+    fn blend(self, other: Self, amount: u8) -> Self {
+        let (r1, g1, b1) = self.to_rgb();
+        let (r2, g2, b2) = other.to_rgb();
+        O::from_rgb(
+            blend8(r1, r2, amount),
+            blend8(g1, g2, amount),
+            blend8(b1, b2, amount),
+        )
+    }
+
+    #[inline(always)]
+    fn lerp(self, other: Self, frac: u8) -> Self {
+        let (r1, g1, b1) = self.to_rgb();
+        let (r2, g2, b2) = other.to_rgb();
+        O::from_rgb(
+            lerp8by8(r1, r2, frac),
+            lerp8by8(g1, g2, frac),
+            lerp8by8(b1, b2, frac),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::RGB;
+    use core::f32::consts::PI;
+
+    /// Runs `check` with every pair of 8-bit values.
+    fn for_all_pairs(mut check: impl FnMut(u8, u8)) {
+        for i in 0..=255 {
+            for j in 0..=255 {
+                check(i, j);
+            }
+        }
+    }
+
+    // The reference formulas below are the C versions from lib8tion.
+
+    fn scale8_ref(i: u8, scale: u8) -> u8 {
+        ((i as u32 * (1 + scale as u32)) >> 8) as u8
+    }
+
+    fn scale8_video_ref(i: u8, scale: u8) -> u8 {
+        let j = (i as u32 * scale as u32) >> 8;
+        (j + (i != 0 && scale != 0) as u32) as u8
+    }
+
+    fn blend8_ref(a: u8, b: u8, amount_of_b: u8) -> u8 {
+        let mut partial = ((a as i32) << 8) | b as i32;
+        partial += b as i32 * amount_of_b as i32;
+        partial -= a as i32 * amount_of_b as i32;
+        (partial >> 8) as u8
+    }
+
+    fn lerp8by8_ref(a: u8, b: u8, frac: u8) -> u8 {
+        if b > a {
+            a + scale8_ref(b - a, frac)
+        } else {
+            a - scale8_ref(a - b, frac)
+        }
+    }
+
+    #[test]
+    fn saturating_arithmetic() {
+        for_all_pairs(|i, j| {
+            assert_eq!(qadd8(i, j) as u32, (i as u32 + j as u32).min(255));
+            assert_eq!(qsub8(i, j) as i32, (i as i32 - j as i32).max(0));
+        });
+    }
+
+    #[test]
+    fn scaling() {
+        for_all_pairs(|i, scale| {
+            assert_eq!(scale8(i, scale), scale8_ref(i, scale), "{} {}", i, scale);
+            assert_eq!(scale8_const(i, scale), scale8_ref(i, scale));
+            assert_eq!(scale8_video(i, scale), scale8_video_ref(i, scale));
+            assert_eq!(scale8_video_const(i, scale), scale8_video_ref(i, scale));
+        });
+        assert_eq!(scale8(200, 255), 200);
+        assert_eq!(scale8_video(1, 1), 1);
+    }
+
+    #[test]
+    fn blending() {
+        for_all_pairs(|a, b| {
+            for amount in 0..=255 {
+                assert_eq!(blend8(a, b, amount), blend8_ref(a, b, amount));
+                assert_eq!(lerp8by8(a, b, amount), lerp8by8_ref(a, b, amount));
+            }
+        });
+    }
+
+    #[test]
+    fn color_math() {
+        for_all_pairs(|i, j| {
+            let color = RGB::from_rgb(i, j, 255 - i);
+            let other = RGB::from_rgb(j, 255 - j, i);
+            let (r, g, b) = color.to_rgb();
+            let (r2, g2, b2) = other.to_rgb();
+
+            let scaled = (scale8_ref(r, j), scale8_ref(g, j), scale8_ref(b, j));
+            assert_eq!(color.scale8(j).to_rgb(), scaled);
+            let mut nscaled = color;
+            nscaled.nscale8(j);
+            assert_eq!(nscaled.to_rgb(), scaled);
+
+            let video = (
+                scale8_video_ref(r, j),
+                scale8_video_ref(g, j),
+                scale8_video_ref(b, j),
+            );
+            assert_eq!(color.scale8_video(j).to_rgb(), video);
+
+            let mut faded = color;
+            faded.fade_to_black_by(j);
+            let fade = 255 - j;
+            assert_eq!(
+                faded.to_rgb(),
+                (
+                    scale8_ref(r, fade),
+                    scale8_ref(g, fade),
+                    scale8_ref(b, fade)
+                )
+            );
+
+            let sum = color.saturating_add(other).to_rgb();
+            assert_eq!(sum, (qadd8(r, r2), qadd8(g, g2), qadd8(b, b2)));
+            let difference = color.saturating_sub(other).to_rgb();
+            assert_eq!(difference, (qsub8(r, r2), qsub8(g, g2), qsub8(b, b2)));
+
+            let blended = (
+                blend8_ref(r, r2, i),
+                blend8_ref(g, g2, i),
+                blend8_ref(b, b2, i),
+            );
+            assert_eq!(color.blend(other, i).to_rgb(), blended);
+            let lerped = (
+                lerp8by8_ref(r, r2, i),
+                lerp8by8_ref(g, g2, i),
+                lerp8by8_ref(b, b2, i),
+            );
+            assert_eq!(color.lerp(other, i).to_rgb(), lerped);
+        });
+    }
+
+    #[test]
+    fn sine() {
+        for theta in 0..=255u8 {
+            let angle = theta as f32 * 2.0 * PI / 256.0;
+            let expected = 128.0 + 127.0 * angle.sin();
+            // The table approximation is off by up to 3.2 steps, like
+            // the one from FastLED.
+            let error = (sin8(theta) as f32 - expected).abs();
+            assert!(
+                error <= 3.2,
+                "sin8({}) = {}, expected {}",
+                theta,
+                sin8(theta),
+                expected
+            );
+            assert_eq!(cos8(theta), sin8(theta.wrapping_add(64)));
+        }
+        assert_eq!(
+            (sin8(0), sin8(64), sin8(128), sin8(192)),
+            (128, 255, 128, 1)
+        );
+    }
+
+    #[test]
+    fn easing() {
+        for i in 0..=255u8 {
+            let x = i as f32 / 255.0;
+            let eased = if x < 0.5 {
+                2.0 * x * x
+            } else {
+                1.0 - 2.0 * (1.0 - x) * (1.0 - x)
+            };
+            let error = (ease8(i) as f32 - eased * 255.0).abs();
+            assert!(
+                error <= 2.0,
+                "ease8({}) = {}, expected {}",
+                i,
+                ease8(i),
+                eased * 255.0
+            );
+        }
+        assert_eq!((ease8(0), ease8(255)), (0, 255));
+    }
+}
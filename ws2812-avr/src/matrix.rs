@@ -0,0 +1,371 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Two-dimensional views over frame buffers, for led matrices. The
+ * mapping from coordinates into positions of the buffer is given by a
+ * [Layout], either the configurable [GridLayout] for regular panels or
+ * a [LookupLayout] for irregular builds. */
+use crate::color::ColorOrder;
+
+/**
+ * Maps the coordinates of a matrix into positions of its frame buffer.
+ */
+pub trait Layout {
+    /// Returns the position of the led at (`x`, `y`) in a matrix of
+    /// `width` by `height` leds. The coordinates are always in range.
+    fn index(&self, x: usize, y: usize, width: usize, height: usize) -> usize;
+}
+
+/// Rotation of a matrix, clockwise, from the way its leds are wired.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    R0,
+    R90,
+    R180,
+    R270,
+}
+
+/**
+ * The layout of a regular panel, whose leds are wired row by row, or
+ * column by column, starting at its top left corner. On progressive
+ * panels, every row starts at the same side, while on serpentine
+ * panels the direction alternates on every row. The coordinates can
+ * be flipped and rotated before being mapped into the panel.
+ */
+#[derive(Clone, Copy)]
+pub struct GridLayout {
+    serpentine: bool,
+    column_major: bool,
+    rotation: Rotation,
+    flip_x: bool,
+    flip_y: bool,
+}
+
+impl GridLayout {
+    /// A progressive layout, wired row by row.
+    pub const fn new() -> Self {
+        GridLayout {
+            serpentine: false,
+            column_major: false,
+            rotation: Rotation::R0,
+            flip_x: false,
+            flip_y: false,
+        }
+    }
+
+    /// Returns the layout, with the direction of its rows (or columns)
+    /// alternating.
+    pub const fn serpentine(self) -> Self {
+        GridLayout {
+            serpentine: true,
+            ..self
+        }
+    }
+
+    /// Returns the layout, wired column by column.
+    pub const fn column_major(self) -> Self {
+        GridLayout {
+            column_major: true,
+            ..self
+        }
+    }
+
+    /// Returns the layout, rotated clockwise. The width and height of
+    /// the matrix are swapped from the ones of the panel when rotating
+    /// it by 90 or 270 degrees.
+    pub const fn rotated(self, rotation: Rotation) -> Self {
+        GridLayout { rotation, ..self }
+    }
+
+    /// Returns the layout, flipped horizontally.
+    pub const fn flipped_x(self) -> Self {
+        GridLayout {
+            flip_x: !self.flip_x,
+            ..self
+        }
+    }
+
+    /// Returns the layout, flipped vertically.
+    pub const fn flipped_y(self) -> Self {
+        GridLayout {
+            flip_y: !self.flip_y,
+            ..self
+        }
+    }
+}
+
+impl Default for GridLayout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Layout for GridLayout {
+    fn index(&self, x: usize, y: usize, width: usize, height: usize) -> usize {
+        let x = if self.flip_x { width - 1 - x } else { x };
+        let y = if self.flip_y { height - 1 - y } else { y };
+
+        // Coordinates and size of the panel, as it is wired.
+        let (px, py, pw, ph) = match self.rotation {
+            Rotation::R0 => (x, y, width, height),
+            Rotation::R90 => (height - 1 - y, x, height, width),
+            Rotation::R180 => (width - 1 - x, height - 1 - y, width, height),
+            Rotation::R270 => (y, width - 1 - x, height, width),
+        };
+
+        if self.column_major {
+            let py = if self.serpentine && px % 2 == 1 {
+                ph - 1 - py
+            } else {
+                py
+            };
+            px * ph + py
+        } else {
+            let px = if self.serpentine && py % 2 == 1 {
+                pw - 1 - px
+            } else {
+                px
+            };
+            py * pw + px
+        }
+    }
+}
+
+/**
+ * The layout of an irregular build, given by a table with the position
+ * of each led, row by row.
+ */
+#[derive(Clone, Copy)]
+pub struct LookupLayout<'a> {
+    table: &'a [u16],
+}
+
+impl<'a> LookupLayout<'a> {
+    /// Creates a layout from a table of `width * height` positions.
+    pub const fn new(table: &'a [u16]) -> Self {
+        LookupLayout { table }
+    }
+}
+
+impl<'a> Layout for LookupLayout<'a> {
+    fn index(&self, x: usize, y: usize, width: usize, _height: usize) -> usize {
+        // Positions missing from the table are mapped out of the buffer.
+        self.table
+            .get(y * width + x)
+            .map_or(usize::MAX, |&index| index as usize)
+    }
+}
+
+/**
+ * A view of a frame buffer as a matrix of `W` by `H` leds, laid out
+ * as given by `L`.
+ */
+pub struct Matrix<'a, O, const W: usize, const H: usize, L> {
+    pixels: &'a mut [O],
+    layout: L,
+}
+
+impl<'a, O: ColorOrder, const W: usize, const H: usize, L: Layout> Matrix<'a, O, W, H, L> {
+    /// Creates a view of the given frame buffer.
+    pub fn new(pixels: &'a mut [O], layout: L) -> Self {
+        Matrix { pixels, layout }
+    }
+
+    /// Width of the matrix, in leds.
+    pub const fn width(&self) -> usize {
+        W
+    }
+
+    /// Height of the matrix, in leds.
+    pub const fn height(&self) -> usize {
+        H
+    }
+
+    /// Returns the position of the led at (`x`, `y`) in the frame
+    /// buffer, or `None` if it is out of the matrix or the buffer.
+    pub fn index(&self, x: usize, y: usize) -> Option<usize> {
+        if x >= W || y >= H {
+            return None;
+        }
+
+        let index = self.layout.index(x, y, W, H);
+        if index < self.pixels.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the color of the led at (`x`, `y`), or `None` if it is
+    /// out of the matrix.
+    pub fn get(&self, x: usize, y: usize) -> Option<O> {
+        self.index(x, y).map(|index| self.pixels[index])
+    }
+
+    /// Returns the led at (`x`, `y`) for modifying it, or `None` if it
+    /// is out of the matrix.
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut O> {
+        self.index(x, y).map(move |index| &mut self.pixels[index])
+    }
+
+    /// Sets the color of the led at (`x`, `y`). Returns `None`, leaving
+    /// the buffer untouched, if it is out of the matrix.
+    pub fn set(&mut self, x: usize, y: usize, color: O) -> Option<()> {
+        self.get_mut(x, y).map(|pixel| *pixel = color)
+    }
+
+    /// Sets all the leds of the buffer to the given color.
+    pub fn fill(&mut self, color: O) {
+        self.pixels.fill(color);
+    }
+
+    /// Returns the underlying frame buffer.
+    pub fn pixels_mut(&mut self) -> &mut [O] {
+        self.pixels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::RGB;
+
+    /// Positions of every led of a `width` by `height` matrix, row by row.
+    fn positions(layout: &impl Layout, width: usize, height: usize) -> Vec<Vec<usize>> {
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| layout.index(x, y, width, height))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn progressive() {
+        let layout = GridLayout::new();
+        assert_eq!(positions(&layout, 3, 2), [[0, 1, 2], [3, 4, 5]]);
+    }
+
+    #[test]
+    fn serpentine() {
+        let layout = GridLayout::new().serpentine();
+        assert_eq!(positions(&layout, 3, 3), [[0, 1, 2], [5, 4, 3], [6, 7, 8]]);
+    }
+
+    #[test]
+    fn column_major() {
+        let layout = GridLayout::new().column_major();
+        assert_eq!(positions(&layout, 3, 2), [[0, 2, 4], [1, 3, 5]]);
+
+        let layout = layout.serpentine();
+        assert_eq!(positions(&layout, 3, 2), [[0, 3, 4], [1, 2, 5]]);
+    }
+
+    #[test]
+    fn rotations() {
+        let layout = GridLayout::new();
+        assert_eq!(
+            positions(&layout.rotated(Rotation::R90), 2, 3),
+            [[2, 5], [1, 4], [0, 3]]
+        );
+        assert_eq!(
+            positions(&layout.rotated(Rotation::R180), 3, 2),
+            [[5, 4, 3], [2, 1, 0]]
+        );
+        assert_eq!(
+            positions(&layout.rotated(Rotation::R270), 2, 3),
+            [[3, 0], [4, 1], [5, 2]]
+        );
+    }
+
+    #[test]
+    fn rotated_serpentine() {
+        let layout = GridLayout::new().serpentine().rotated(Rotation::R90);
+        assert_eq!(positions(&layout, 2, 3), [[2, 3], [1, 4], [0, 5]]);
+    }
+
+    #[test]
+    fn flips() {
+        let layout = GridLayout::new();
+        assert_eq!(positions(&layout.flipped_x(), 3, 2), [[2, 1, 0], [5, 4, 3]]);
+        assert_eq!(positions(&layout.flipped_y(), 3, 2), [[3, 4, 5], [0, 1, 2]]);
+        assert_eq!(
+            positions(&layout.flipped_x().flipped_x(), 3, 2),
+            positions(&layout, 3, 2)
+        );
+        assert_eq!(
+            positions(&layout.flipped_x().flipped_y(), 3, 2),
+            positions(&layout.rotated(Rotation::R180), 3, 2)
+        );
+    }
+
+    #[test]
+    fn every_layout_maps_every_led_once() {
+        let rotations = [Rotation::R0, Rotation::R90, Rotation::R180, Rotation::R270];
+        for options in 0..16 {
+            for rotation in rotations {
+                let mut layout = GridLayout::new().rotated(rotation);
+                if options & 1 != 0 {
+                    layout = layout.serpentine();
+                }
+                if options & 2 != 0 {
+                    layout = layout.column_major();
+                }
+                if options & 4 != 0 {
+                    layout = layout.flipped_x();
+                }
+                if options & 8 != 0 {
+                    layout = layout.flipped_y();
+                }
+
+                for (width, height) in [(1, 1), (4, 3), (3, 4), (5, 5)] {
+                    let mut leds: Vec<usize> = positions(&layout, width, height).concat();
+                    leds.sort_unstable();
+                    assert!(leds.into_iter().eq(0..width * height));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let layout = LookupLayout::new(&[3, 0, 2, 1]);
+        assert_eq!(positions(&layout, 2, 2), [[3, 0], [2, 1]]);
+
+        // The table is shorter than the matrix.
+        assert_eq!(positions(&layout, 2, 3)[2], [usize::MAX, usize::MAX]);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let mut pixels = [RGB::from_rgb(0, 0, 0); 5];
+        let mut matrix = Matrix::<RGB, 3, 2, _>::new(&mut pixels, GridLayout::new());
+
+        assert_eq!(matrix.index(2, 1), None);
+        assert_eq!(matrix.index(1, 1), Some(4));
+        assert_eq!(matrix.index(3, 0), None);
+        assert_eq!(matrix.index(0, 2), None);
+
+        assert_eq!(matrix.set(2, 1, RGB::from_rgb(1, 2, 3)), None);
+        assert_eq!(matrix.set(1, 1, RGB::from_rgb(1, 2, 3)), Some(()));
+        assert_eq!(
+            matrix.get(1, 1).map(|color| color.to_rgb()),
+            Some((1, 2, 3))
+        );
+        assert_eq!(pixels.map(|color| color.to_rgb())[..4], [(0, 0, 0); 4]);
+    }
+}
@@ -0,0 +1,392 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+/*! Color palettes, that map an 8-bit index into a color, in the style
+ * of the palettes from FastLED. Palettes can be stored either in RAM,
+ * or in the program memory by wrapping them into a [ProgMem]. */
+use crate::color::{ColorOrder, RGB};
+use crate::math8::{blend8, ColorMath};
+use crate::util::progmem::ProgMem;
+use core::mem::size_of;
+
+/**
+ * A source of colors indexed by an 8-bit value.
+ */
+pub trait Palette<O: ColorOrder> {
+    /// Number of entries of the palette.
+    const LEN: usize;
+
+    /// Returns the `n`-th entry of the palette, wrapping around past [Palette::LEN].
+    fn entry(&self, n: u8) -> O;
+
+    /**
+     * Returns the color at the given index, where the range 0-255 is
+     * spread across all the entries of the palette. When `blend` is
+     * enabled and the index falls between two entries, the color is
+     * linearly interpolated between them, wrapping around the end of
+     * the palette.
+     */
+    fn color(&self, index: u8, blend: bool) -> O;
+}
+
+/**
+ * A palette of 16 colors, whose lookups can be blended between
+ * entries to give 256 different colors. Takes 48 bytes of memory.
+ */
+#[derive(Clone, Copy)]
+pub struct Palette16<O> {
+    pub entries: [O; 16],
+}
+
+/**
+ * A palette of 256 colors, one for each index. Takes 768 bytes of
+ * memory, so it should usually be stored in the program memory.
+ */
+#[derive(Clone, Copy)]
+pub struct Palette256<O> {
+    pub entries: [O; 256],
+}
+
+/**
+ * A stop of a gradient palette definition: the color that the
+ * gradient takes at the given index. Stops must be sorted by index,
+ * and the first and last stops should be at 0 and 255 respectively.
+ */
+#[derive(Clone, Copy)]
+pub struct GradientStop {
+    pub index: u8,
+    pub color: RGB,
+}
+
+impl GradientStop {
+    pub const fn new(index: u8, r: u8, g: u8, b: u8) -> Self {
+        GradientStop {
+            index,
+            color: RGB { r, g, b },
+        }
+    }
+}
+
+/// Builds a color from a 0xRRGGBB value.
+const fn from_hex<O: ~const ColorOrder>(hex: u32) -> O {
+    O::from_rgb((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+}
+
+/// Computes the color of a gradient at the given position.
+const fn gradient_color<O: ~const ColorOrder>(stops: &[GradientStop], pos: u8) -> O {
+    let mut i = 0;
+    while i + 1 < stops.len() && stops[i + 1].index < pos {
+        i += 1;
+    }
+
+    let from = stops[i];
+    if i + 1 == stops.len() || pos <= from.index {
+        return O::from_rgb(from.color.r, from.color.g, from.color.b);
+    }
+
+    let to = stops[i + 1];
+    let amount = ((pos - from.index) as u16 * 255 / (to.index - from.index) as u16) as u8;
+    O::from_rgb(
+        blend8(from.color.r, to.color.r, amount),
+        blend8(from.color.g, to.color.g, amount),
+        blend8(from.color.b, to.color.b, amount),
+    )
+}
+
+impl<O: ColorOrder> Palette16<O> {
+    pub const fn new(entries: [O; 16]) -> Self {
+        Palette16 { entries }
+    }
+
+    /// Builds a palette from a list of colors in the 0xRRGGBB format.
+    pub const fn from_hex(hex: [u32; 16]) -> Self
+    where
+        O: ~const ColorOrder,
+    {
+        let mut entries = [O::from_rgb(0, 0, 0); 16];
+        let mut i = 0;
+        while i < 16 {
+            entries[i] = from_hex(hex[i]);
+            i += 1;
+        }
+
+        Palette16 { entries }
+    }
+
+    /// Builds a palette by sampling a gradient definition at 16 evenly spaced points.
+    pub const fn from_gradient(stops: &[GradientStop]) -> Self
+    where
+        O: ~const ColorOrder,
+    {
+        let mut entries = [O::from_rgb(0, 0, 0); 16];
+        let mut i = 0;
+        while i < 16 {
+            entries[i] = gradient_color(stops, (i * 17) as u8);
+            i += 1;
+        }
+
+        Palette16 { entries }
+    }
+
+    /// Converts the palette into a palette with a different color order.
+    pub const fn convert<T: ~const ColorOrder>(self) -> Palette16<T>
+    where
+        O: ~const ColorOrder,
+    {
+        let mut entries = [T::from_rgb(0, 0, 0); 16];
+        let mut i = 0;
+        while i < 16 {
+            let (r, g, b) = self.entries[i].to_rgb();
+            entries[i] = T::from_rgb(r, g, b);
+            i += 1;
+        }
+
+        Palette16 { entries }
+    }
+}
+
+impl<O: ColorOrder> Palette256<O> {
+    pub const fn new(entries: [O; 256]) -> Self {
+        Palette256 { entries }
+    }
+
+    /// Builds a palette by sampling a gradient definition at every index.
+    pub const fn from_gradient(stops: &[GradientStop]) -> Self
+    where
+        O: ~const ColorOrder,
+    {
+        let mut entries = [O::from_rgb(0, 0, 0); 256];
+        let mut i = 0;
+        while i < 256 {
+            entries[i] = gradient_color(stops, i as u8);
+            i += 1;
+        }
+
+        Palette256 { entries }
+    }
+
+    /// Builds a palette by expanding a 16 color palette, blending between its entries.
+    pub fn from_palette16(palette: &Palette16<O>) -> Self {
+        let mut entries = [O::from_rgb(0, 0, 0); 256];
+        for (i, entry) in entries.iter_mut().enumerate() {
+            *entry = palette.color(i as u8, true);
+        }
+
+        Palette256 { entries }
+    }
+}
+
+/// Looks up a color into a 16 entry palette whose entries are read
+/// with the given function.
+#[inline(always)]
+fn color16<O: ColorOrder>(entry: impl Fn(u8) -> O, index: u8, blend: bool) -> O {
+    let hi4 = index >> 4;
+    let lo4 = index & 0x0F;
+    let color = entry(hi4);
+
+    if blend && lo4 != 0 {
+        color.blend(entry((hi4 + 1) & 0x0F), lo4 << 4)
+    } else {
+        color
+    }
+}
+
+impl<O: ColorOrder> Palette<O> for Palette16<O> {
+    const LEN: usize = 16;
+
+    #[inline(always)]
+    fn entry(&self, n: u8) -> O {
+        self.entries[(n & 0x0F) as usize]
+    }
+
+    #[inline(always)]
+    fn color(&self, index: u8, blend: bool) -> O {
+        color16(|n| self.entry(n), index, blend)
+    }
+}
+
+impl<O: ColorOrder> Palette<O> for Palette256<O> {
+    const LEN: usize = 256;
+
+    #[inline(always)]
+    fn entry(&self, n: u8) -> O {
+        self.entries[n as usize]
+    }
+
+    #[inline(always)]
+    fn color(&self, index: u8, _blend: bool) -> O {
+        self.entry(index)
+    }
+}
+
+impl<O: ColorOrder> Palette<O> for ProgMem<Palette16<O>> {
+    const LEN: usize = 16;
+
+    #[inline(always)]
+    fn entry(&self, n: u8) -> O {
+        // SAFETY: Entries are stored contiguously at the beginning of
+        // the palette, and the masked index is in range.
+        unsafe { self.read_at((n & 0x0F) as usize * size_of::<O>()) }
+    }
+
+    #[inline(always)]
+    fn color(&self, index: u8, blend: bool) -> O {
+        color16(|n| self.entry(n), index, blend)
+    }
+}
+
+impl<O: ColorOrder> Palette<O> for ProgMem<Palette256<O>> {
+    const LEN: usize = 256;
+
+    #[inline(always)]
+    fn entry(&self, n: u8) -> O {
+        // SAFETY: Entries are stored contiguously at the beginning of
+        // the palette, and every u8 is a valid index.
+        unsafe { self.read_at(n as usize * size_of::<O>()) }
+    }
+
+    #[inline(always)]
+    fn color(&self, index: u8, _blend: bool) -> O {
+        self.entry(index)
+    }
+}
+
+/// Hues of the color wheel, with the same width for every one of them.
+pub const RAINBOW: Palette16<RGB> = Palette16::from_hex([
+    0xFF0000, 0xD52A00, 0xAB5500, 0xAB7F00, 0xABAB00, 0x56D500, 0x00FF00, 0x00D52A, 0x00AB55,
+    0x0056AA, 0x0000FF, 0x2A00D5, 0x5500AB, 0x7F0081, 0xAB0055, 0xD5002B,
+]);
+
+/// Black, then red, yellow and white, like the colors of a flame.
+pub const HEAT: Palette16<RGB> = Palette16::from_hex([
+    0x000000, 0x330000, 0x660000, 0x990000, 0xCC0000, 0xFF0000, 0xFF3300, 0xFF6600, 0xFF9900,
+    0xFFCC00, 0xFFFF00, 0xFFFF33, 0xFFFF66, 0xFFFF99, 0xFFFFCC, 0xFFFFFF,
+]);
+
+/// Blues and greens of the sea.
+pub const OCEAN: Palette16<RGB> = Palette16::from_hex([
+    0x191970, 0x00008B, 0x191970, 0x000080, 0x00008B, 0x0000CD, 0x2E8B57, 0x008080, 0x5F9EA0,
+    0x0000FF, 0x008B8B, 0x6495ED, 0x7FFFD4, 0x2E8B57, 0x00FFFF, 0x87CEFA,
+]);
+
+/// Reds, oranges and whites of lava, with dark spots.
+pub const LAVA: Palette16<RGB> = Palette16::from_hex([
+    0x000000, 0x800000, 0x000000, 0x800000, 0x8B0000, 0x8B0000, 0x800000, 0x8B0000, 0x8B0000,
+    0x8B0000, 0xFF0000, 0xFFA500, 0xFFFFFF, 0xFFA500, 0xFF0000, 0x8B0000,
+]);
+
+/// A rainbow that skips the greens, for brighter and warmer colors.
+pub const PARTY: Palette16<RGB> = Palette16::from_hex([
+    0x5500AB, 0x84007C, 0xB5004B, 0xE5001B, 0xE81700, 0xB84700, 0xAB7700, 0xABAB00, 0xAB5500,
+    0xDD2200, 0xF2000E, 0xC2003E, 0x8F0071, 0x5F00A1, 0x2F00D0, 0x0007F9,
+]);
+
+/// Gradient definition going from black to red, then yellow and white.
+pub const HEAT_GRADIENT: [GradientStop; 4] = [
+    GradientStop::new(0, 0, 0, 0),
+    GradientStop::new(128, 255, 0, 0),
+    GradientStop::new(224, 255, 255, 0),
+    GradientStop::new(255, 255, 255, 255),
+];
+
+/// Gradient definition of a sunset, going from dark blue to orange and yellow.
+pub const SUNSET_GRADIENT: [GradientStop; 5] = [
+    GradientStop::new(0, 10, 0, 40),
+    GradientStop::new(64, 90, 0, 90),
+    GradientStop::new(128, 220, 20, 40),
+    GradientStop::new(192, 255, 110, 0),
+    GradientStop::new(255, 255, 220, 60),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette16_entries_wrap_around() {
+        crate::progmem! {
+            static PALETTE: Palette16<RGB> = RAINBOW;
+        }
+        for n in 0..=255 {
+            let expected = RAINBOW.entries[n as usize % 16].to_rgb();
+            assert_eq!(RAINBOW.entry(n).to_rgb(), expected);
+            assert_eq!(PALETTE.entry(n).to_rgb(), expected);
+        }
+    }
+
+    #[test]
+    fn palette16_blends_between_entries() {
+        crate::progmem! {
+            static PALETTE: Palette16<RGB> = RAINBOW;
+        }
+        let cases = [
+            (0x10, true, (213, 42, 0)),
+            (0x18, false, (213, 42, 0)),
+            (0x18, true, (192, 63, 0)),
+            // The last entry blends into the first one.
+            (0xF8, true, (234, 0, 21)),
+        ];
+        for (index, blend, expected) in cases {
+            assert_eq!(RAINBOW.color(index, blend).to_rgb(), expected);
+            assert_eq!(PALETTE.color(index, blend).to_rgb(), expected);
+        }
+    }
+
+    #[test]
+    fn palette256_lookup() {
+        crate::progmem! {
+            static PALETTE: Palette256<RGB> = Palette256::from_gradient(&HEAT_GRADIENT);
+        }
+        let palette = Palette256::<RGB>::from_gradient(&HEAT_GRADIENT);
+        let expanded = Palette256::from_palette16(&HEAT);
+        for index in 0..=255 {
+            let expected = gradient_color::<RGB>(&HEAT_GRADIENT, index).to_rgb();
+            for blend in [false, true] {
+                assert_eq!(palette.color(index, blend).to_rgb(), expected);
+                assert_eq!(PALETTE.color(index, blend).to_rgb(), expected);
+            }
+            assert_eq!(
+                expanded.color(index, false).to_rgb(),
+                HEAT.color(index, true).to_rgb()
+            );
+        }
+    }
+
+    #[test]
+    fn gradients() {
+        let heat = |pos| gradient_color::<RGB>(&HEAT_GRADIENT, pos).to_rgb();
+        assert_eq!(heat(0), (0, 0, 0));
+        assert_eq!(heat(64), (127, 0, 0));
+        assert_eq!(heat(128), (255, 0, 0));
+        assert_eq!(heat(176), (255, 127, 0));
+        assert_eq!(heat(224), (255, 255, 0));
+        assert_eq!(heat(255), (255, 255, 255));
+
+        let sunset = |pos| gradient_color::<RGB>(&SUNSET_GRADIENT, pos).to_rgb();
+        assert_eq!(sunset(0), (10, 0, 40));
+        assert_eq!(sunset(32), (50, 0, 65));
+        assert_eq!(sunset(128), (220, 20, 40));
+        assert_eq!(sunset(255), (255, 220, 60));
+
+        let palette = Palette16::<RGB>::from_gradient(&HEAT_GRADIENT);
+        assert_eq!(palette.entries[0].to_rgb(), (0, 0, 0));
+        assert_eq!(palette.entries[15].to_rgb(), (255, 255, 255));
+        for (i, entry) in palette.entries.iter().enumerate() {
+            assert_eq!(entry.to_rgb(), heat(i as u8 * 17));
+        }
+    }
+}
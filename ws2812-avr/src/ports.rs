@@ -14,6 +14,62 @@ General Public License for more details.
 You should have received a copy of the GNU General Public License
 along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
  */
+#[cfg(feature = "atmega-hal")]
+use atmega_hal as hal;
+#[cfg(feature = "attiny-hal")]
+use attiny_hal as hal;
+
+/**
+ * The type of the pin handle the driver takes ownership of. On the
+ * devices supported by avr-hal it is the output pin provided by it.
+ * On the rest of devices, like the megaAVR-0 and tinyAVR-0/1/2
+ * families, it is one of the pin types defined at [pins].
+ */
+#[cfg(not(feature = "raw-pins"))]
+pub type OutputPin<P> = avr_hal_generic::port::Pin<avr_hal_generic::port::mode::Output, P>;
+
+#[cfg(feature = "raw-pins")]
+pub type OutputPin<P> = P;
+
+// Exactly one MCU feature must be enabled, as each one defines its own
+// set of [StaticPin]s.
+const _: () = match cfg!(feature = "atmega8") as usize
+    + cfg!(feature = "atmega16") as usize
+    + cfg!(feature = "atmega32") as usize
+    + cfg!(feature = "atmega32u4") as usize
+    + cfg!(feature = "atmega48p") as usize
+    + cfg!(feature = "atmega168") as usize
+    + cfg!(feature = "atmega328p") as usize
+    + cfg!(feature = "atmega328pb") as usize
+    + cfg!(feature = "atmega644p") as usize
+    + cfg!(feature = "atmega1280") as usize
+    + cfg!(feature = "atmega1284p") as usize
+    + cfg!(feature = "atmega2560") as usize
+    + cfg!(feature = "atmega4808") as usize
+    + cfg!(feature = "atmega4809") as usize
+    + cfg!(feature = "attiny44") as usize
+    + cfg!(feature = "attiny84") as usize
+    + cfg!(feature = "attiny85") as usize
+    + cfg!(feature = "attiny88") as usize
+    + cfg!(feature = "attiny804") as usize
+    + cfg!(feature = "attiny1614") as usize
+    + cfg!(feature = "attiny1616") as usize
+    + cfg!(feature = "attiny1624") as usize
+    + cfg!(feature = "attiny1626") as usize
+    + cfg!(feature = "attiny2313") as usize
+    + cfg!(feature = "attiny3216") as usize
+    + cfg!(feature = "attiny3224") as usize
+    + cfg!(feature = "attiny3226") as usize
+{
+    0 => panic!(
+        "No MCU feature is enabled for ws2812-avr. Enable the feature that matches your device."
+    ),
+    1 => (),
+    _ => panic!(
+        "Several MCU features are enabled for ws2812-avr. Only one of them can be enabled at once."
+    ),
+};
+
 pub trait StaticPort {
     unsafe fn read() -> u8;
     unsafe fn write(value: u8);
@@ -24,6 +80,7 @@ pub trait StaticPin {
     type Port: StaticPort;
 }
 
+#[cfg(not(feature = "raw-pins"))]
 macro_rules! static_pins {
     {$(
 	($portt:ident, $portn:ident): {
@@ -31,7 +88,7 @@ macro_rules! static_pins {
 	}
     ),*} => {
 	$(
-	    impl StaticPort for arduino_hal::pac::$portt {
+	    impl StaticPort for hal::pac::$portt {
 		#[inline(always)]
 		unsafe fn read() -> u8 {
 		    (*Self::PTR).$portn.read().bits()
@@ -44,10 +101,12 @@ macro_rules! static_pins {
 	    }
 
 	    $(
-		impl StaticPin for arduino_hal::hal::port::$pin {
+		impl StaticPin for hal::port::$pin {
 		    const PIN_BIT_INDEX: u8 = $index;
-		    type Port = arduino_hal::pac::$portt;
+		    type Port = hal::pac::$portt;
 		}
+
+		const _: () = assert!($index < 8, "Pin bit index out of range");
 	    )*
 	)*
 
@@ -357,3 +416,423 @@ static_pins! {
 	(PD7, 7)
     }
 }
+
+/**
+ * Pins of the devices that are not supported by avr-hal. They are
+ * driven directly through the I/O addresses of their direction and
+ * output registers, using the single-cycle `in`, `out` and `sbi`
+ * instructions.
+ *
+ * On the megaAVR-0 and tinyAVR-0/1/2 families, the VPORTx registers
+ * are used, as they are the ones mapped into the low I/O space. The
+ * regular PORTx registers of these cores live in the extended I/O
+ * space and would require an `sts`.
+ */
+#[cfg(feature = "raw-pins")]
+pub mod pins {
+    use super::{StaticPin, StaticPort};
+    use core::arch::asm;
+
+    macro_rules! raw_pins {
+	{$(
+	    ($portt:ident, dir: $dir:literal, out: $out:literal): {
+		$(($pin:ident, $index:literal)),*
+	    }
+	),*} => {
+	    $(
+		pub struct $portt {}
+
+		impl StaticPort for $portt {
+		    #[inline(always)]
+		    unsafe fn read() -> u8 {
+			let value: u8;
+			asm!("in {value}, {addr}", value = out(reg) value, addr = const $out);
+			value
+		    }
+
+		    #[inline(always)]
+		    unsafe fn write(value: u8) {
+			asm!("out {addr}, {value}", addr = const $out, value = in(reg) value);
+		    }
+		}
+
+		$(
+		    pub struct $pin {
+			_private: (),
+		    }
+
+		    impl $pin {
+			/**
+			 * Configures the pin as an output and returns a
+			 * handle to it. This is unsafe because the
+			 * caller must make sure that nothing else is
+			 * driving the same pin.
+			 */
+			#[inline(always)]
+			pub unsafe fn steal_output() -> Self {
+			    asm!("sbi {addr}, {bit}", addr = const $dir, bit = const $index);
+			    $pin { _private: () }
+			}
+		    }
+
+		    const _: () = assert!($index < 8, "Pin bit index out of range");
+
+		    impl StaticPin for $pin {
+			const PIN_BIT_INDEX: u8 = $index;
+			type Port = $portt;
+		    }
+		)*
+	    )*
+	};
+    }
+
+    #[rustfmt::skip]
+    #[cfg(feature = "atmega4809")]
+    raw_pins! {
+	(VPORTA, dir: 0x00, out: 0x01): {
+	    (PA0, 0),
+	    (PA1, 1),
+	    (PA2, 2),
+	    (PA3, 3),
+	    (PA4, 4),
+	    (PA5, 5),
+	    (PA6, 6),
+	    (PA7, 7)
+	},
+
+	(VPORTB, dir: 0x04, out: 0x05): {
+	    (PB0, 0),
+	    (PB1, 1),
+	    (PB2, 2),
+	    (PB3, 3),
+	    (PB4, 4),
+	    (PB5, 5)
+	},
+
+	(VPORTC, dir: 0x08, out: 0x09): {
+	    (PC0, 0),
+	    (PC1, 1),
+	    (PC2, 2),
+	    (PC3, 3),
+	    (PC4, 4),
+	    (PC5, 5),
+	    (PC6, 6),
+	    (PC7, 7)
+	},
+
+	(VPORTD, dir: 0x0C, out: 0x0D): {
+	    (PD0, 0),
+	    (PD1, 1),
+	    (PD2, 2),
+	    (PD3, 3),
+	    (PD4, 4),
+	    (PD5, 5),
+	    (PD6, 6),
+	    (PD7, 7)
+	},
+
+	(VPORTE, dir: 0x10, out: 0x11): {
+	    (PE0, 0),
+	    (PE1, 1),
+	    (PE2, 2),
+	    (PE3, 3)
+	},
+
+	(VPORTF, dir: 0x14, out: 0x15): {
+	    (PF0, 0),
+	    (PF1, 1),
+	    (PF2, 2),
+	    (PF3, 3),
+	    (PF4, 4),
+	    (PF5, 5),
+	    (PF6, 6)
+	}
+    }
+
+    #[rustfmt::skip]
+    #[cfg(feature = "atmega4808")]
+    raw_pins! {
+	(VPORTA, dir: 0x00, out: 0x01): {
+	    (PA0, 0),
+	    (PA1, 1),
+	    (PA2, 2),
+	    (PA3, 3),
+	    (PA4, 4),
+	    (PA5, 5),
+	    (PA6, 6),
+	    (PA7, 7)
+	},
+
+	(VPORTC, dir: 0x08, out: 0x09): {
+	    (PC0, 0),
+	    (PC1, 1),
+	    (PC2, 2),
+	    (PC3, 3)
+	},
+
+	(VPORTD, dir: 0x0C, out: 0x0D): {
+	    (PD0, 0),
+	    (PD1, 1),
+	    (PD2, 2),
+	    (PD3, 3),
+	    (PD4, 4),
+	    (PD5, 5),
+	    (PD6, 6),
+	    (PD7, 7)
+	},
+
+	(VPORTF, dir: 0x14, out: 0x15): {
+	    (PF0, 0),
+	    (PF1, 1),
+	    (PF6, 6)
+	}
+    }
+
+    #[rustfmt::skip]
+    #[cfg(any(
+        feature = "attiny804",
+        feature = "attiny1614",
+        feature = "attiny1624",
+        feature = "attiny3224"
+    ))]
+    raw_pins! {
+	(VPORTA, dir: 0x00, out: 0x01): {
+	    (PA0, 0),
+	    (PA1, 1),
+	    (PA2, 2),
+	    (PA3, 3),
+	    (PA4, 4),
+	    (PA5, 5),
+	    (PA6, 6),
+	    (PA7, 7)
+	},
+
+	(VPORTB, dir: 0x04, out: 0x05): {
+	    (PB0, 0),
+	    (PB1, 1),
+	    (PB2, 2),
+	    (PB3, 3)
+	}
+    }
+
+    #[rustfmt::skip]
+    #[cfg(any(
+        feature = "attiny1616",
+        feature = "attiny1626",
+        feature = "attiny3216",
+        feature = "attiny3226"
+    ))]
+    raw_pins! {
+	(VPORTA, dir: 0x00, out: 0x01): {
+	    (PA0, 0),
+	    (PA1, 1),
+	    (PA2, 2),
+	    (PA3, 3),
+	    (PA4, 4),
+	    (PA5, 5),
+	    (PA6, 6),
+	    (PA7, 7)
+	},
+
+	(VPORTB, dir: 0x04, out: 0x05): {
+	    (PB0, 0),
+	    (PB1, 1),
+	    (PB2, 2),
+	    (PB3, 3),
+	    (PB4, 4),
+	    (PB5, 5)
+	},
+
+	(VPORTC, dir: 0x08, out: 0x09): {
+	    (PC0, 0),
+	    (PC1, 1),
+	    (PC2, 2),
+	    (PC3, 3)
+	}
+    }
+
+    #[rustfmt::skip]
+    #[cfg(any(feature = "atmega644p", feature = "atmega1284p"))]
+    raw_pins! {
+	(PORTA, dir: 0x01, out: 0x02): {
+	    (PA0, 0),
+	    (PA1, 1),
+	    (PA2, 2),
+	    (PA3, 3),
+	    (PA4, 4),
+	    (PA5, 5),
+	    (PA6, 6),
+	    (PA7, 7)
+	},
+
+	(PORTB, dir: 0x04, out: 0x05): {
+	    (PB0, 0),
+	    (PB1, 1),
+	    (PB2, 2),
+	    (PB3, 3),
+	    (PB4, 4),
+	    (PB5, 5),
+	    (PB6, 6),
+	    (PB7, 7)
+	},
+
+	(PORTC, dir: 0x07, out: 0x08): {
+	    (PC0, 0),
+	    (PC1, 1),
+	    (PC2, 2),
+	    (PC3, 3),
+	    (PC4, 4),
+	    (PC5, 5),
+	    (PC6, 6),
+	    (PC7, 7)
+	},
+
+	(PORTD, dir: 0x0A, out: 0x0B): {
+	    (PD0, 0),
+	    (PD1, 1),
+	    (PD2, 2),
+	    (PD3, 3),
+	    (PD4, 4),
+	    (PD5, 5),
+	    (PD6, 6),
+	    (PD7, 7)
+	}
+    }
+
+    #[rustfmt::skip]
+    #[cfg(feature = "atmega8")]
+    raw_pins! {
+	(PORTB, dir: 0x17, out: 0x18): {
+	    (PB0, 0),
+	    (PB1, 1),
+	    (PB2, 2),
+	    (PB3, 3),
+	    (PB4, 4),
+	    (PB5, 5),
+	    (PB6, 6),
+	    (PB7, 7)
+	},
+
+	(PORTC, dir: 0x14, out: 0x15): {
+	    (PC0, 0),
+	    (PC1, 1),
+	    (PC2, 2),
+	    (PC3, 3),
+	    (PC4, 4),
+	    (PC5, 5),
+	    (PC6, 6)
+	},
+
+	(PORTD, dir: 0x11, out: 0x12): {
+	    (PD0, 0),
+	    (PD1, 1),
+	    (PD2, 2),
+	    (PD3, 3),
+	    (PD4, 4),
+	    (PD5, 5),
+	    (PD6, 6),
+	    (PD7, 7)
+	}
+    }
+
+    #[rustfmt::skip]
+    #[cfg(any(feature = "atmega16", feature = "atmega32"))]
+    raw_pins! {
+	(PORTA, dir: 0x1A, out: 0x1B): {
+	    (PA0, 0),
+	    (PA1, 1),
+	    (PA2, 2),
+	    (PA3, 3),
+	    (PA4, 4),
+	    (PA5, 5),
+	    (PA6, 6),
+	    (PA7, 7)
+	},
+
+	(PORTB, dir: 0x17, out: 0x18): {
+	    (PB0, 0),
+	    (PB1, 1),
+	    (PB2, 2),
+	    (PB3, 3),
+	    (PB4, 4),
+	    (PB5, 5),
+	    (PB6, 6),
+	    (PB7, 7)
+	},
+
+	(PORTC, dir: 0x14, out: 0x15): {
+	    (PC0, 0),
+	    (PC1, 1),
+	    (PC2, 2),
+	    (PC3, 3),
+	    (PC4, 4),
+	    (PC5, 5),
+	    (PC6, 6),
+	    (PC7, 7)
+	},
+
+	(PORTD, dir: 0x11, out: 0x12): {
+	    (PD0, 0),
+	    (PD1, 1),
+	    (PD2, 2),
+	    (PD3, 3),
+	    (PD4, 4),
+	    (PD5, 5),
+	    (PD6, 6),
+	    (PD7, 7)
+	}
+    }
+
+    #[rustfmt::skip]
+    #[cfg(any(feature = "attiny44", feature = "attiny84"))]
+    raw_pins! {
+	(PORTA, dir: 0x1A, out: 0x1B): {
+	    (PA0, 0),
+	    (PA1, 1),
+	    (PA2, 2),
+	    (PA3, 3),
+	    (PA4, 4),
+	    (PA5, 5),
+	    (PA6, 6),
+	    (PA7, 7)
+	},
+
+	(PORTB, dir: 0x17, out: 0x18): {
+	    (PB0, 0),
+	    (PB1, 1),
+	    (PB2, 2),
+	    (PB3, 3)
+	}
+    }
+
+    #[rustfmt::skip]
+    #[cfg(feature = "attiny2313")]
+    raw_pins! {
+	(PORTA, dir: 0x1A, out: 0x1B): {
+	    (PA0, 0),
+	    (PA1, 1),
+	    (PA2, 2)
+	},
+
+	(PORTB, dir: 0x17, out: 0x18): {
+	    (PB0, 0),
+	    (PB1, 1),
+	    (PB2, 2),
+	    (PB3, 3),
+	    (PB4, 4),
+	    (PB5, 5),
+	    (PB6, 6),
+	    (PB7, 7)
+	},
+
+	(PORTD, dir: 0x11, out: 0x12): {
+	    (PD0, 0),
+	    (PD1, 1),
+	    (PD2, 2),
+	    (PD3, 3),
+	    (PD4, 4),
+	    (PD5, 5),
+	    (PD6, 6)
+	}
+    }
+}
@@ -0,0 +1,161 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+/*! Estimation of the current drawn by a strip for a given frame, that
+ * allows limiting the brightness of the frames so they stay under a
+ * current budget, like the one of an USB port. */
+use crate::color::ColorOrder;
+
+/**
+ * Describes the current drawn by each led of a strip, in microamps.
+ * The draw of a channel is assumed to be linear with its value.
+ */
+#[derive(Clone, Copy)]
+pub struct PowerModel {
+    /// Current drawn by the red channel at full value.
+    pub red_ua: u16,
+    /// Current drawn by the green channel at full value.
+    pub green_ua: u16,
+    /// Current drawn by the blue channel at full value.
+    pub blue_ua: u16,
+    /// Current drawn by each led when it is off.
+    pub idle_ua: u16,
+}
+
+impl PowerModel {
+    /// Typical values for WS2812B leds, as measured by the FastLED project.
+    pub const WS2812B: PowerModel = PowerModel {
+        red_ua: 16000,
+        green_ua: 11000,
+        blue_ua: 15000,
+        idle_ua: 1000,
+    };
+
+    /// Estimates the current drawn by the leds while the given frame
+    /// is being displayed, in microamps, excluding their idle current.
+    fn active_ua<O: ColorOrder>(&self, pixels: &[O]) -> u32 {
+        let (mut r, mut g, mut b) = (0u32, 0u32, 0u32);
+        for pixel in pixels {
+            let (pr, pg, pb) = pixel.to_rgb();
+            r += pr as u32;
+            g += pg as u32;
+            b += pb as u32;
+        }
+
+        ((r as u64 * self.red_ua as u64
+            + g as u64 * self.green_ua as u64
+            + b as u64 * self.blue_ua as u64)
+            / 255) as u32
+    }
+
+    /// Estimates the current drawn by the leds while they are off, in microamps.
+    fn idle_ua(&self, leds: usize) -> u32 {
+        leds as u32 * self.idle_ua as u32
+    }
+
+    /// Estimates the total current drawn by the leds while the given
+    /// frame is being displayed, in milliamps.
+    pub fn estimate_ma<O: ColorOrder>(&self, pixels: &[O]) -> u32 {
+        (self.active_ua(pixels) + self.idle_ua(pixels.len())) / 1000
+    }
+
+    /**
+     * Computes the highest brightness, to be applied through
+     * [crate::math8::scale8], that keeps the draw of the given frame
+     * under `budget_ma` milliamps. Returns 255 if the frame already
+     * fits the budget, and 0 if not even the idle current does.
+     */
+    pub fn max_brightness<O: ColorOrder>(&self, pixels: &[O], budget_ma: u32) -> u8 {
+        let active = self.active_ua(pixels);
+        let idle = self.idle_ua(pixels.len());
+        let budget = budget_ma.saturating_mul(1000);
+
+        if active + idle <= budget {
+            return 255;
+        }
+        if active == 0 {
+            // Only the idle current is left, and it is over budget.
+            return 0;
+        }
+
+        // scale8 multiplies by (brightness + 1) / 256.
+        let available = budget.saturating_sub(idle) as u64;
+        let scale = available * 256 / active as u64;
+        scale.saturating_sub(1) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::RGB;
+    use crate::math8::ColorMath;
+
+    const MODEL: PowerModel = PowerModel::WS2812B;
+
+    #[test]
+    fn estimate() {
+        let white = [RGB::from_rgb(255, 255, 255); 10];
+        assert_eq!(MODEL.estimate_ma(&white), 430);
+        let black = [RGB::from_rgb(0, 0, 0); 10];
+        assert_eq!(MODEL.estimate_ma(&black), 10);
+        let red = [RGB::from_rgb(128, 0, 0); 10];
+        assert_eq!(MODEL.estimate_ma(&red), 10 + 80);
+        assert_eq!(MODEL.estimate_ma::<RGB>(&[]), 0);
+    }
+
+    #[test]
+    fn fits_budget() {
+        let white = [RGB::from_rgb(255, 255, 255); 10];
+        assert_eq!(MODEL.max_brightness(&white, 430), 255);
+        assert_eq!(MODEL.max_brightness(&white, 10_000), 255);
+        assert_eq!(MODEL.max_brightness::<RGB>(&[], 0), 255);
+    }
+
+    #[test]
+    fn idle_over_budget() {
+        let black = [RGB::from_rgb(0, 0, 0); 600];
+        assert_eq!(MODEL.max_brightness(&black, 500), 0);
+        let white = [RGB::from_rgb(255, 255, 255); 600];
+        assert_eq!(MODEL.max_brightness(&white, 500), 0);
+    }
+
+    #[test]
+    fn scaled_frame_fits_budget() {
+        let mut frame = [RGB::from_rgb(0, 0, 0); 60];
+        for (i, pixel) in frame.iter_mut().enumerate() {
+            let i = i as u8;
+            *pixel = RGB::from_rgb(i * 4, 255 - i * 4, i * 2);
+        }
+
+        for budget in (100..1500).step_by(50) {
+            let brightness = MODEL.max_brightness(&frame, budget);
+            let scaled = frame.map(|pixel| pixel.scale8(brightness));
+            let estimate = MODEL.estimate_ma(&scaled);
+            assert!(estimate <= budget, "{} mA over {} mA", estimate, budget);
+            if brightness < 255 {
+                // Rounding down the channels only wastes a few milliamps.
+                assert!(
+                    budget - estimate <= 10,
+                    "{} mA under {} mA",
+                    estimate,
+                    budget
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,253 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Fixed-rate animations paced by a hardware timer. A timer is set up
+ * to fire a compare interrupt `FPS` times per second, whose handler
+ * counts the ticks into a [FrameTicker]. A [Scheduler] waits for every
+ * tick before rendering the next frame of an effect and sending it,
+ * so the frame rate doesn't depend on how long rendering takes, and
+ * frames that could not be rendered in time are counted as dropped.
+ *
+ * ```no_run
+ * static TICKER: FrameTicker = FrameTicker::new();
+ *
+ * #[avr_device::interrupt(atmega328p)]
+ * fn TIMER1_COMPA() {
+ *     TICKER.tick();
+ * }
+ *
+ * let mut scheduler = start_timer1::<60>(dp.TC1, &TICKER);
+ * unsafe { avr_device::interrupt::enable() };
+ *
+ * loop {
+ *     scheduler.run_frame(&mut strip, &mut effect);
+ * }
+ * ```
+ *
+ * The interrupt handler is left to the application, as the interrupt
+ * attribute depends on the device. */
+use crate::color::ColorOrder;
+use crate::consts::NANOS_IN_SECOND;
+use crate::effects::Effect;
+use crate::ports::StaticPin;
+use crate::strip::Strip;
+use crate::{frame_nanos_estimate, Timings, TypedTimings};
+use avr_hal_generic::avr_device::interrupt::{free, Mutex};
+use core::cell::Cell;
+use core::marker::PhantomData;
+
+/**
+ * Counts the ticks of the timer that have not been handled yet. Meant
+ * to be declared as a static, that is ticked from the interrupt
+ * handler of the timer.
+ */
+pub struct FrameTicker {
+    pending: Mutex<Cell<u8>>,
+}
+
+impl FrameTicker {
+    pub const fn new() -> Self {
+        FrameTicker {
+            pending: Mutex::new(Cell::new(0)),
+        }
+    }
+
+    /// Registers a tick of the timer. To be called from its interrupt
+    /// handler.
+    #[inline(always)]
+    pub fn tick(&self) {
+        free(|cs| {
+            let pending = self.pending.borrow(cs);
+            pending.set(pending.get().saturating_add(1));
+        });
+    }
+
+    /// Returns the number of ticks since the last call, clearing them.
+    fn take(&self) -> u8 {
+        free(|cs| self.pending.borrow(cs).replace(0))
+    }
+}
+
+/**
+ * Runs an effect at `FPS` frames per second, as given by the ticks of
+ * a [FrameTicker]. It is returned by the function that starts the
+ * timer, like [start_timer1], so that its rate is the one the timer
+ * was set up with.
+ */
+pub struct Scheduler<const FPS: u32> {
+    ticker: &'static FrameTicker,
+    tick: u32,
+    dropped: u32,
+}
+
+impl<const FPS: u32> Scheduler<FPS> {
+    /// Creates a scheduler for a timer set up by the application,
+    /// whose interrupt handler must tick `ticker` `FPS` times per
+    /// second.
+    pub const fn new(ticker: &'static FrameTicker) -> Self {
+        Scheduler {
+            ticker,
+            tick: 0,
+            dropped: 0,
+        }
+    }
+
+    /**
+     * Waits for the next tick of the timer, renders the next frame of
+     * the effect into the strip and sends it. If several ticks went by
+     * since the previous frame, the frames in between are dropped, and
+     * the effect is moved forward to the current one so that it keeps
+     * its pace.
+     *
+     * Fails to compile if sending the `N` leds of the strip takes
+     * longer than a frame.
+     */
+    pub fn run_frame<P, Ts, O, E, const N: usize>(
+        &mut self,
+        strip: &mut Strip<P, Ts, O, N>,
+        effect: &mut E,
+    ) where
+        P: StaticPin,
+        Ts: TypedTimings,
+        O: ColorOrder,
+        E: Effect<O>,
+    {
+        #[allow(clippy::let_unit_value)]
+        let () = FrameFits::<Ts, O, N, FPS>::CHECK;
+
+        let mut pending = self.ticker.take();
+        while pending == 0 {
+            pending = self.ticker.take();
+        }
+
+        self.dropped += pending as u32 - 1;
+        self.tick = self.tick.wrapping_add(pending as u32 - 1);
+        effect.step(strip.pixels_mut(), self.tick);
+        strip.show();
+        self.tick = self.tick.wrapping_add(1);
+    }
+
+    /// Number of the next frame to be rendered.
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// Number of frames dropped so far because rendering and sending
+    /// the previous ones took too long.
+    pub fn dropped_frames(&self) -> u32 {
+        self.dropped
+    }
+}
+
+/// Checks at compile time that a frame of `N` leds can be sent within
+/// the period of the frame rate, according to [frame_nanos_estimate].
+struct FrameFits<Ts, O, const N: usize, const FPS: u32>(PhantomData<(Ts, O)>);
+
+impl<Ts: Timings, O, const N: usize, const FPS: u32> FrameFits<Ts, O, N, FPS> {
+    const CHECK: () = assert!(
+        FPS > 0 && frame_nanos_estimate::<Ts, O>(N) < NANOS_IN_SECOND / FPS as u64,
+        "The strip is too long for being sent at this frame rate."
+    );
+}
+
+/// Prescaler and compare value that make a timer, whose counter goes
+/// up to `MAX`, fire `FPS` compare interrupts per second.
+#[cfg(feature = "atmega-hal")]
+struct TimerSetup<const FPS: u32, const MAX: u32>;
+
+#[cfg(feature = "atmega-hal")]
+impl<const FPS: u32, const MAX: u32> TimerSetup<FPS, MAX> {
+    const SETUP: (u32, u32) = timer_setup(FPS, MAX);
+}
+
+/// Finds the smallest prescaler that allows a timer whose counter goes
+/// up to `max` to fire `fps` times per second, as it gives the most
+/// accurate rate, and returns it along with its compare value.
+#[cfg(feature = "atmega-hal")]
+const fn timer_setup(fps: u32, max: u32) -> (u32, u32) {
+    const PRESCALERS: [u32; 5] = [1, 8, 64, 256, 1024];
+
+    assert!(fps > 0, "The frame rate must be greater than zero.");
+    let mut i = 0;
+    while i < PRESCALERS.len() {
+        let counts = crate::consts::F_CPU / PRESCALERS[i] / fps;
+        if counts > 0 && counts - 1 <= max {
+            return (PRESCALERS[i], counts - 1);
+        }
+        i += 1;
+    }
+
+    panic!("The frame rate is too low for this timer at the current CPU frequency.")
+}
+
+/**
+ * Sets up Timer1 for firing its compare A interrupt (`TIMER1_COMPA`)
+ * `FPS` times per second, in CTC mode, and returns the scheduler that
+ * runs at that rate from the ticks of `ticker`. Fails to compile if
+ * the timer cannot reach that rate at the CPU frequency.
+ */
+#[cfg(feature = "atmega-hal")]
+pub fn start_timer1<const FPS: u32>(
+    tc1: atmega_hal::pac::TC1,
+    ticker: &'static FrameTicker,
+) -> Scheduler<FPS> {
+    let (prescaler, compare) = TimerSetup::<FPS, 0xFFFF>::SETUP;
+
+    tc1.tccr1a.write(|w| w.wgm1().bits(0b00));
+    tc1.ocr1a.write(|w| w.bits(compare as u16));
+    tc1.tccr1b.write(|w| {
+        let w = w.wgm1().bits(0b01);
+        match prescaler {
+            1 => w.cs1().direct(),
+            8 => w.cs1().prescale_8(),
+            64 => w.cs1().prescale_64(),
+            256 => w.cs1().prescale_256(),
+            _ => w.cs1().prescale_1024(),
+        }
+    });
+    tc1.timsk1.modify(|_, w| w.ocie1a().set_bit());
+
+    Scheduler::new(ticker)
+}
+
+/**
+ * Sets up Timer0 for firing its compare A interrupt (`TIMER0_COMPA`)
+ * `FPS` times per second, in CTC mode, and returns the scheduler that
+ * runs at that rate from the ticks of `ticker`. As its counter only has 8 bits,
+ * it cannot produce rates lower than about 61 Hz at 16 MHz, and fails
+ * to compile if it cannot reach the given one. Note that Timer0 is
+ * also the one used for keeping time by some libraries.
+ */
+#[cfg(feature = "atmega-hal")]
+pub fn start_timer0<const FPS: u32>(
+    tc0: atmega_hal::pac::TC0,
+    ticker: &'static FrameTicker,
+) -> Scheduler<FPS> {
+    let (prescaler, compare) = TimerSetup::<FPS, 0xFF>::SETUP;
+
+    tc0.tccr0a.write(|w| w.wgm0().ctc());
+    tc0.ocr0a.write(|w| w.bits(compare as u8));
+    tc0.tccr0b.write(|w| match prescaler {
+        1 => w.cs0().direct(),
+        8 => w.cs0().prescale_8(),
+        64 => w.cs0().prescale_64(),
+        256 => w.cs0().prescale_256(),
+        _ => w.cs0().prescale_1024(),
+    });
+    tc0.timsk0.modify(|_, w| w.ocie0a().set_bit());
+
+    Scheduler::new(ticker)
+}
@@ -0,0 +1,400 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Logical segments of a frame buffer, that allow splitting a single
+ * strip into independent zones, and virtual strips made of several
+ * physical ones. */
+use crate::color::ColorOrder;
+use crate::ports::StaticPin;
+use crate::{TypedTimings, WS2812};
+
+/**
+ * A range of leds of a strip, meant to be declared as a named constant
+ * for each zone of an installation:
+ *
+ * ```no_run
+ * const SHELF_TOP: Segment = Segment::new(0, 30);
+ * const SHELF_BOTTOM: Segment = Segment::new(30, 30).reversed();
+ * ```
+ *
+ * A reversed segment counts its leds from its end, and a mirrored
+ * segment repeats its first half, reversed, on its second half.
+ */
+#[derive(Clone, Copy)]
+pub struct Segment {
+    start: usize,
+    len: usize,
+    reversed: bool,
+    mirrored: bool,
+}
+
+impl Segment {
+    /// Creates a segment of `len` leds starting at the led `start`.
+    pub const fn new(start: usize, len: usize) -> Self {
+        Segment {
+            start,
+            len,
+            reversed: false,
+            mirrored: false,
+        }
+    }
+
+    /// Returns the segment, with the direction of its leds reversed.
+    pub const fn reversed(self) -> Self {
+        Segment {
+            reversed: !self.reversed,
+            ..self
+        }
+    }
+
+    /// Returns the segment, with its first half mirrored on its second
+    /// half.
+    pub const fn mirrored(self) -> Self {
+        Segment {
+            mirrored: true,
+            ..self
+        }
+    }
+
+    /// Index of the first led of the segment in the strip.
+    pub const fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Number of logical leds of the segment, that is half of its
+    /// physical leds, rounded up, for mirrored segments.
+    pub const fn len(&self) -> usize {
+        if self.mirrored {
+            (self.len + 1) / 2
+        } else {
+            self.len
+        }
+    }
+
+    /// Returns whether the segment has no leds.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a view of the segment over the given frame buffer.
+    ///
+    /// Panics if the segment does not fit into the buffer.
+    pub fn view<'a, O: ColorOrder>(&self, pixels: &'a mut [O]) -> SegmentView<'a, O> {
+        SegmentView {
+            pixels: &mut pixels[self.start..self.start + self.len],
+            reversed: self.reversed,
+            mirrored: self.mirrored,
+        }
+    }
+}
+
+/**
+ * A segment over a frame buffer, that maps its logical leds into the
+ * physical ones.
+ */
+pub struct SegmentView<'a, O> {
+    pixels: &'a mut [O],
+    reversed: bool,
+    mirrored: bool,
+}
+
+impl<'a, O: ColorOrder> SegmentView<'a, O> {
+    /// Number of logical leds of the segment.
+    pub fn len(&self) -> usize {
+        if self.mirrored {
+            (self.pixels.len() + 1) / 2
+        } else {
+            self.pixels.len()
+        }
+    }
+
+    /// Returns whether the segment has no leds.
+    pub fn is_empty(&self) -> bool {
+        self.pixels.is_empty()
+    }
+
+    /// Maps a logical led into its physical position in the segment.
+    fn physical(&self, i: usize) -> usize {
+        if self.reversed {
+            self.pixels.len() - 1 - i
+        } else {
+            i
+        }
+    }
+
+    /// Returns the color of the `i`-th logical led, or `None` if it is
+    /// out of the segment.
+    pub fn get(&self, i: usize) -> Option<O> {
+        if i < self.len() {
+            Some(self.pixels[self.physical(i)])
+        } else {
+            None
+        }
+    }
+
+    /// Sets the color of the `i`-th logical led, along with its mirror
+    /// if the segment is mirrored. Leds out of the segment are ignored.
+    pub fn set(&mut self, i: usize, color: O) {
+        if i < self.len() {
+            let physical = self.physical(i);
+            self.pixels[physical] = color;
+            if self.mirrored {
+                let mirror = self.pixels.len() - 1 - physical;
+                self.pixels[mirror] = color;
+            }
+        }
+    }
+
+    /// Sets all the leds of the segment to the given color.
+    pub fn fill(&mut self, color: O) {
+        self.pixels.fill(color);
+    }
+
+    /**
+     * Calls `f` with the logical leds of the segment as a plain slice,
+     * in their logical order, so that effects written for a whole strip
+     * can be applied to it. The leds are rearranged in place, without
+     * copying them into another buffer, and the contents of the slice
+     * are kept between calls.
+     */
+    pub fn with_pixels<R>(&mut self, f: impl FnOnce(&mut [O]) -> R) -> R {
+        let len = self.len();
+        if self.reversed {
+            self.pixels.reverse();
+        }
+
+        let result = f(&mut self.pixels[..len]);
+
+        if self.mirrored {
+            for i in 0..len {
+                let mirror = self.pixels.len() - 1 - i;
+                self.pixels[mirror] = self.pixels[i];
+            }
+        }
+        if self.reversed {
+            self.pixels.reverse();
+        }
+
+        result
+    }
+}
+
+/**
+ * A destination frames can be written to, either a physical strip or
+ * a virtual one made of several physical strips.
+ */
+pub trait StripOutput<O> {
+    /// Writes a frame to the leds.
+    fn write(&mut self, data: &[O]);
+}
+
+impl<P: StaticPin, Ts: TypedTimings, Order: ColorOrder> StripOutput<Order>
+    for WS2812<P, Ts, Order>
+{
+    fn write(&mut self, data: &[Order]) {
+        WS2812::write(self, data);
+    }
+}
+
+/**
+ * A virtual strip made of two outputs, that sends the first `split`
+ * leds of a frame to the first output and the rest of them to the
+ * second one. Chains can be nested for joining more than two outputs.
+ */
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+    split: usize,
+}
+
+impl<A, B> Chain<A, B> {
+    /// Joins two outputs, the first of them having `split` leds.
+    pub const fn new(first: A, split: usize, second: B) -> Self {
+        Chain {
+            first,
+            second,
+            split,
+        }
+    }
+
+    /// Destroys the chain, returning its outputs.
+    pub fn release(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<O, A: StripOutput<O>, B: StripOutput<O>> StripOutput<O> for Chain<A, B> {
+    fn write(&mut self, data: &[O]) {
+        let split = self.split.min(data.len());
+        self.first.write(&data[..split]);
+        self.second.write(&data[split..]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::RGB;
+    use crate::Dummy;
+
+    /// A frame buffer whose leds have their index as red.
+    fn numbered(len: u8) -> Vec<RGB> {
+        (0..len).map(|i| RGB::from_rgb(i, 0, 0)).collect()
+    }
+
+    /// The red of every led of the buffer.
+    fn reds(pixels: &[RGB]) -> Vec<u8> {
+        pixels.iter().map(|color| color.to_rgb().0).collect()
+    }
+
+    fn red(value: u8) -> RGB {
+        RGB::from_rgb(value, 0, 0)
+    }
+
+    #[test]
+    fn forward() {
+        let mut pixels = numbered(8);
+        let mut view = Segment::new(2, 4).view(&mut pixels);
+        assert_eq!(view.len(), 4);
+        assert_eq!(view.get(0).map(|c| c.to_rgb().0), Some(2));
+        assert!(view.get(4).is_none());
+
+        view.set(1, red(100));
+        view.set(4, red(200));
+        assert_eq!(reds(&pixels), [0, 1, 2, 100, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn reversed() {
+        let mut pixels = numbered(8);
+        let mut view = Segment::new(2, 4).reversed().view(&mut pixels);
+        assert_eq!(view.get(0).map(|c| c.to_rgb().0), Some(5));
+        assert_eq!(view.get(3).map(|c| c.to_rgb().0), Some(2));
+
+        view.set(0, red(100));
+        assert_eq!(reds(&pixels), [0, 1, 2, 3, 4, 100, 6, 7]);
+
+        // Reversing twice gives back the original direction.
+        let mut view = Segment::new(2, 4).reversed().reversed().view(&mut pixels);
+        view.set(0, red(200));
+        assert_eq!(reds(&pixels), [0, 1, 200, 3, 4, 100, 6, 7]);
+    }
+
+    #[test]
+    fn mirrored() {
+        let mut pixels = numbered(5);
+        let mut view = Segment::new(0, 5).mirrored().view(&mut pixels);
+        assert_eq!(view.len(), 3);
+        assert!(view.get(3).is_none());
+
+        view.set(0, red(100));
+        view.set(1, red(101));
+        view.set(2, red(102));
+        assert_eq!(reds(&pixels), [100, 101, 102, 101, 100]);
+
+        let mut pixels = numbered(4);
+        let mut view = Segment::new(0, 4).mirrored().reversed().view(&mut pixels);
+        assert_eq!(view.len(), 2);
+        view.set(0, red(100));
+        assert_eq!(reds(&pixels), [100, 1, 2, 100]);
+
+        let mut view = Segment::new(0, 4).mirrored().reversed().view(&mut pixels);
+        view.set(1, red(101));
+        assert_eq!(reds(&pixels), [100, 101, 101, 100]);
+    }
+
+    #[test]
+    fn with_pixels() {
+        let mut pixels = numbered(8);
+        let mut view = Segment::new(2, 4).reversed().view(&mut pixels);
+        let logical = view.with_pixels(|leds| reds(leds));
+        assert_eq!(logical, [5, 4, 3, 2]);
+        assert_eq!(reds(&pixels), [0, 1, 2, 3, 4, 5, 6, 7]);
+
+        let mut view = Segment::new(2, 4).reversed().view(&mut pixels);
+        view.with_pixels(|leds| {
+            for (i, led) in leds.iter_mut().enumerate() {
+                *led = red(100 + i as u8);
+            }
+        });
+        assert_eq!(reds(&pixels), [0, 1, 103, 102, 101, 100, 6, 7]);
+    }
+
+    #[test]
+    fn with_pixels_mirrored() {
+        let mut pixels = numbered(7);
+        let mut view = Segment::new(1, 5).mirrored().view(&mut pixels);
+        let len = view.with_pixels(|leds| {
+            for (i, led) in leds.iter_mut().enumerate() {
+                *led = red(100 + i as u8);
+            }
+            leds.len()
+        });
+        assert_eq!(len, 3);
+        assert_eq!(reds(&pixels), [0, 100, 101, 102, 101, 100, 6]);
+
+        // The logical leds keep their contents between calls.
+        let mut view = Segment::new(1, 5).mirrored().view(&mut pixels);
+        let logical = view.with_pixels(|leds| reds(leds));
+        assert_eq!(logical, [100, 101, 102]);
+
+        let mut view = Segment::new(1, 5).mirrored().reversed().view(&mut pixels);
+        let logical = view.with_pixels(|leds| reds(leds));
+        assert_eq!(logical, [100, 101, 102]);
+    }
+
+    #[test]
+    fn empty() {
+        let mut pixels = numbered(4);
+        for segment in [
+            Segment::new(2, 0),
+            Segment::new(2, 0).reversed(),
+            Segment::new(2, 0).mirrored(),
+            Segment::new(2, 0).mirrored().reversed(),
+        ] {
+            let mut view = segment.view(&mut pixels);
+            assert!(view.is_empty());
+            assert!(view.get(0).is_none());
+            view.set(0, red(100));
+            assert_eq!(view.with_pixels(|leds| leds.len()), 0);
+        }
+        assert_eq!(reds(&pixels), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn chain() {
+        let mut chain = Chain::new(
+            WS2812::<Dummy, Dummy, RGB>::default(),
+            3,
+            Chain::new(
+                WS2812::<Dummy, Dummy, RGB>::default(),
+                2,
+                WS2812::<Dummy, Dummy, RGB>::default(),
+            ),
+        );
+        chain.write(&numbered(7));
+        chain.write(&numbered(4));
+
+        let (first, rest) = chain.release();
+        let (second, third) = rest.release();
+        let frames = |output: &WS2812<Dummy, Dummy, RGB>| -> Vec<Vec<u8>> {
+            output.frames.iter().map(|frame| reds(frame)).collect()
+        };
+        assert_eq!(frames(&first), [vec![0, 1, 2], vec![0, 1, 2]]);
+        assert_eq!(frames(&second), [vec![3, 4], vec![3]]);
+        assert_eq!(frames(&third), [vec![5, 6], vec![]]);
+    }
+}
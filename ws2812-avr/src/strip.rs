@@ -0,0 +1,155 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! A strip of a fixed number of leds, that owns both the frame buffer
+ * and the driver used to send it. */
+use crate::color::ColorOrder;
+use crate::ports::StaticPin;
+use crate::util::{BExpr, IsTrue};
+use crate::{TypedTimings, WS2812};
+use core::ops::{Index, IndexMut};
+
+/**
+ * A strip of `N` leds, holding a frame buffer of `N` colors that is
+ * sent through its driver with [Strip::show].
+ */
+pub struct Strip<P, Ts, Order, const N: usize> {
+    driver: WS2812<P, Ts, Order>,
+    pixels: [Order; N],
+}
+
+impl<P: StaticPin, Ts: TypedTimings, Order: ColorOrder, const N: usize> Strip<P, Ts, Order, N> {
+    /// Creates a strip with all of its leds off.
+    pub fn new(driver: WS2812<P, Ts, Order>) -> Self {
+        Strip {
+            driver,
+            pixels: [Order::from_rgb(0, 0, 0); N],
+        }
+    }
+
+    /// Number of leds of the strip.
+    pub const fn len(&self) -> usize {
+        N
+    }
+
+    /// Returns whether the strip has no leds.
+    pub const fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    /// Returns the frame buffer of the strip.
+    pub fn pixels(&self) -> &[Order; N] {
+        &self.pixels
+    }
+
+    /// Returns the frame buffer of the strip, for modifying it.
+    pub fn pixels_mut(&mut self) -> &mut [Order; N] {
+        &mut self.pixels
+    }
+
+    /// Returns the driver of the strip, for changing its settings or
+    /// writing the buffer through any of its methods.
+    pub fn driver_mut(&mut self) -> &mut WS2812<P, Ts, Order> {
+        &mut self.driver
+    }
+
+    /// Destroys the strip, returning its driver.
+    pub fn into_driver(self) -> WS2812<P, Ts, Order> {
+        self.driver
+    }
+
+    /// Sets all the leds to the given color.
+    pub fn fill(&mut self, color: Order) {
+        self.pixels.fill(color);
+    }
+
+    /// Turns all the leds off.
+    pub fn clear(&mut self) {
+        self.fill(Order::from_rgb(0, 0, 0));
+    }
+
+    /// Sends the frame buffer to the leds.
+    pub fn show(&mut self) {
+        self.driver.write(&self.pixels);
+    }
+
+    /// Sends the frame buffer to the leds, scaling it by
+    /// `brightness / 256`. See [WS2812::write_scaled].
+    pub fn show_scaled(&mut self, brightness: u8) {
+        self.driver.write_scaled(&self.pixels, brightness);
+    }
+
+    /// Moves the leds `count` positions towards the start of the strip,
+    /// wrapping the first ones around to its end.
+    pub fn rotate_left(&mut self, count: usize) {
+        if N > 0 {
+            self.pixels.rotate_left(count % N);
+        }
+    }
+
+    /// Moves the leds `count` positions towards the end of the strip,
+    /// wrapping the last ones around to its start.
+    pub fn rotate_right(&mut self, count: usize) {
+        if N > 0 {
+            self.pixels.rotate_right(count % N);
+        }
+    }
+
+    /// Reverses the order of the leds.
+    pub fn reverse(&mut self) {
+        self.pixels.reverse();
+    }
+
+    /**
+     * Returns the `LEN` leds starting at `START` as a fixed-size
+     * segment of the buffer. The segment is checked to fit into the
+     * strip at compile time.
+     */
+    pub fn segment<const START: usize, const LEN: usize>(&self) -> &[Order; LEN]
+    where
+        BExpr<{ START + LEN <= N }>: IsTrue,
+    {
+        // SAFETY: the segment fits into the buffer, as checked above.
+        unsafe { &*(self.pixels.as_ptr().add(START) as *const [Order; LEN]) }
+    }
+
+    /**
+     * Returns the `LEN` leds starting at `START` as a fixed-size
+     * segment of the buffer, for modifying them. The segment is checked
+     * to fit into the strip at compile time.
+     */
+    pub fn segment_mut<const START: usize, const LEN: usize>(&mut self) -> &mut [Order; LEN]
+    where
+        BExpr<{ START + LEN <= N }>: IsTrue,
+    {
+        // SAFETY: the segment fits into the buffer, as checked above.
+        unsafe { &mut *(self.pixels.as_mut_ptr().add(START) as *mut [Order; LEN]) }
+    }
+}
+
+impl<P, Ts, Order, const N: usize> Index<usize> for Strip<P, Ts, Order, N> {
+    type Output = Order;
+
+    fn index(&self, index: usize) -> &Order {
+        &self.pixels[index]
+    }
+}
+
+impl<P, Ts, Order, const N: usize> IndexMut<usize> for Strip<P, Ts, Order, N> {
+    fn index_mut(&mut self, index: usize) -> &mut Order {
+        &mut self.pixels[index]
+    }
+}
@@ -0,0 +1,118 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! A human-readable summary of a driver configuration, built at
+ * compile time, for checking the timings that are actually achieved
+ * with the CPU frequency, either by printing it over serial or by
+ * comparing it in a test. */
+use crate::consts;
+use crate::util::const_str::ConstStrBuf;
+use crate::util::time::TimeVal;
+use crate::{frame_nanos_estimate, CalculatedTimings, Timings};
+use core::marker::PhantomData;
+use core::mem::size_of;
+use ws2812_timings::Achieved;
+
+/// Capacity of the buffer the summary is first built into, before
+/// moving it into one of its exact length.
+const SUMMARY_CAPACITY: usize = 256;
+
+/**
+ * The configuration summary of a driver with the timings `Ts` and the
+ * color order `Order`, driving `N` leds:
+ *
+ * ```no_run
+ * let summary: &'static str = ConfigSummary::<DefaultTimings, GRB, 60>::TEXT;
+ * ```
+ *
+ * Which gives, at 16 MHz:
+ *
+ * ```text
+ * F_CPU: 16000000 Hz
+ * Cycles per bit: 20
+ * NOPs (S1/S2/S3): 3/7/2
+ * T0h: 312 ns (350 ns requested)
+ * T1h: 875 ns (900 ns requested)
+ * Cycle: 1250 ns (1250 ns requested)
+ * Bytes per pixel: 3
+ * Frame buffer for 60 leds: 180 bytes
+ * Frame for 60 leds: 2151 us
+ * ```
+ *
+ * The summary is only stored in the program when it is used, and then
+ * takes as many bytes as its text, both in the flash and in the RAM,
+ * as constant data is copied into the RAM on startup.
+ */
+pub struct ConfigSummary<Ts, Order, const N: usize> {
+    _ts: PhantomData<Ts>,
+    _order: PhantomData<Order>,
+}
+
+impl<Ts: Timings + 'static, Order: 'static, const N: usize> ConfigSummary<Ts, Order, N>
+where
+    [(); summary_len::<Ts, Order, N>()]:,
+{
+    const BUF: &'static ConstStrBuf<{ summary_len::<Ts, Order, N>() }> =
+        &build::<Ts, Order, N>().resize();
+
+    /// The text of the summary, one value per line.
+    pub const TEXT: &'static str = Self::BUF.as_str();
+}
+
+/// Length of the text of a summary.
+pub const fn summary_len<Ts: Timings, Order, const N: usize>() -> usize {
+    build::<Ts, Order, N>().len()
+}
+
+/// Builds the text of a summary into a buffer large enough for any.
+const fn build<Ts: Timings, Order, const N: usize>() -> ConstStrBuf<SUMMARY_CAPACITY> {
+    let achieved = Achieved::new(consts::F_CPU, &crate::calculate::<Ts>());
+
+    ConstStrBuf::new()
+        .push_str("F_CPU: ")
+        .push_num(consts::F_CPU as u64)
+        .push_str(" Hz\nCycles per bit: ")
+        .push_num(Ts::BIT_CYCLES as u64)
+        .push_str("\nNOPs (S1/S2/S3): ")
+        .push_num(Ts::S1_NOPS as u64)
+        .push_str("/")
+        .push_num(Ts::S2_NOPS as u64)
+        .push_str("/")
+        .push_num(Ts::S3_NOPS as u64)
+        .push_str("\nT0h: ")
+        .push_num(achieved.t0h)
+        .push_str(" ns (")
+        .push_num(Ts::T0h::NANOS)
+        .push_str(" ns requested)\nT1h: ")
+        .push_num(achieved.t1h)
+        .push_str(" ns (")
+        .push_num(Ts::T1h::NANOS)
+        .push_str(" ns requested)\nCycle: ")
+        .push_num(achieved.cycle)
+        .push_str(" ns (")
+        .push_num(Ts::Cycle::NANOS)
+        .push_str(" ns requested)\nBytes per pixel: ")
+        .push_num(size_of::<Order>() as u64)
+        .push_str("\nFrame buffer for ")
+        .push_num(N as u64)
+        .push_str(" leds: ")
+        .push_num((N * size_of::<Order>()) as u64)
+        .push_str(" bytes\nFrame for ")
+        .push_num(N as u64)
+        .push_str(" leds: ")
+        .push_num(frame_nanos_estimate::<Ts, Order>(N) / 1000)
+        .push_str(" us")
+}
@@ -0,0 +1,571 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Receiver of the TPM2 protocol, spoken over serial by lighting
+ * software such as Glediator or Jinx!. Every packet is made of a start
+ * byte, its type, the size of its payload as a big endian 16 bits
+ * number, the payload and an end byte:
+ *
+ * ```text
+ * 0xC9 type hi lo payload... 0x36
+ * ```
+ *
+ * Data packets hold the red, green and blue components of every led.
+ * Command packets hold a command for the device, which is left to the
+ * application. The packets of TPM2.net, which start with `0x9C`, are
+ * understood too: they have the number of the packet and the number
+ * of packets of the frame after the size, so a frame can be split
+ * into several packets, that must be received in order.
+ *
+ * Packets have no checksum, so a packet that lost some bytes is only
+ * found out when the byte after its payload is not the end byte. By
+ * then, the start of the next packet has been taken as payload, so
+ * that packet is lost too, and the parser looks for a start byte from
+ * there on. That byte may be found in the payload of the lost packet,
+ * which gives more errors until the parser lands on the start of a
+ * packet again.
+ *
+ * ```no_run
+ * let mut pixels = [GRB::default(); LED_COUNT];
+ * let mut receiver = Tpm2::new(&mut pixels);
+ * loop {
+ *     if let Ok(Packet::Command) = nb::block!(receiver.update(&mut serial, &mut driver)) {
+ *         handle_command(receiver.command());
+ *     }
+ * }
+ * ```
+ */
+use crate::color::ColorOrder;
+use crate::ports::StaticPin;
+use crate::{TypedTimings, WS2812};
+use embedded_hal::serial::Read;
+
+/// Start byte of the TPM2 packets.
+pub const START: u8 = 0xC9;
+/// Start byte of the TPM2.net packets.
+pub const START_NET: u8 = 0x9C;
+/// End byte of every packet.
+pub const END: u8 = 0x36;
+
+/// Type of the packets with the colors of a frame.
+pub const TYPE_DATA: u8 = 0xDA;
+/// Type of the packets with a command for the device.
+pub const TYPE_COMMAND: u8 = 0xC0;
+/// Type of the packets with the response to a command.
+pub const TYPE_RESPONSE: u8 = 0xAA;
+
+/// Largest payload of the command packets that can be received.
+pub const COMMAND_CAPACITY: usize = 16;
+
+/// A packet received completely.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Packet {
+    /// The last packet of a frame, with the number of leds of it that
+    /// have been stored into the pixels.
+    Frame(usize),
+    /// A command packet, whose payload is available through
+    /// [Tpm2::command].
+    Command,
+}
+
+/// An error found while receiving a packet. The packet, and the frame
+/// it belongs to, are dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The end byte was not found after the payload, so the packet had
+    /// less bytes than its size, or it was corrupt. The next packet is
+    /// usually lost along with it.
+    Short,
+    /// The payload does not fit into the pixels, or into the buffer of
+    /// commands. It is skipped.
+    Oversized(u16),
+    /// A TPM2.net packet arrived out of order, or with a packet number
+    /// greater than the number of packets of the frame.
+    OutOfSequence { expected: u8, received: u8 },
+    /// The type of the packet is not known. Bytes are skipped until
+    /// the next start byte.
+    UnknownType(u8),
+}
+
+/// An error returned while reading packets from a serial port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReceiveError<E> {
+    Serial(E),
+    Protocol(Error),
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Start,
+    Type,
+    SizeHi,
+    SizeLo,
+    Number,
+    Count,
+    Payload,
+    End,
+}
+
+/**
+ * A parser of the TPM2 protocol, that stores the colors of every frame
+ * into a buffer of pixels.
+ */
+pub struct Tpm2<'a, O> {
+    pixels: &'a mut [O],
+    state: State,
+    net: bool,
+    kind: u8,
+    size: u16,
+    number: u8,
+    count: u8,
+    read: u16,
+    /// Number of the next TPM2.net packet of the frame.
+    next_packet: u8,
+    /// Offset into the frame, in bytes, of the current packet.
+    offset: usize,
+    rgb: [u8; 3],
+    error: Option<Error>,
+    command: [u8; COMMAND_CAPACITY],
+    command_len: usize,
+}
+
+impl<'a, O: ColorOrder> Tpm2<'a, O> {
+    pub fn new(pixels: &'a mut [O]) -> Self {
+        Tpm2 {
+            pixels,
+            state: State::Start,
+            net: false,
+            kind: 0,
+            size: 0,
+            number: 0,
+            count: 0,
+            read: 0,
+            next_packet: 1,
+            offset: 0,
+            rgb: [0; 3],
+            error: None,
+            command: [0; COMMAND_CAPACITY],
+            command_len: 0,
+        }
+    }
+
+    /// The pixels, holding the colors of the last frame received and,
+    /// while a frame is being received, some of the colors of it.
+    pub fn pixels(&self) -> &[O] {
+        self.pixels
+    }
+
+    /// The payload of the last command packet received.
+    pub fn command(&self) -> &[u8] {
+        &self.command[..self.command_len]
+    }
+
+    /// Drops the packet and the frame being received, if any, waiting
+    /// for the next start byte.
+    pub fn reset(&mut self) {
+        self.state = State::Start;
+        self.next_packet = 1;
+    }
+
+    /**
+     * Parses the next byte of the stream. Returns the packet when it
+     * is the last byte of it, or the error found in the packet.
+     */
+    pub fn push(&mut self, byte: u8) -> Result<Option<Packet>, Error> {
+        match self.state {
+            State::Start => self.start(byte),
+            State::Type => {
+                if byte != TYPE_DATA && byte != TYPE_COMMAND && byte != TYPE_RESPONSE {
+                    self.reset();
+                    return Err(Error::UnknownType(byte));
+                }
+                self.kind = byte;
+                self.state = State::SizeHi;
+            }
+            State::SizeHi => {
+                self.size = (byte as u16) << 8;
+                self.state = State::SizeLo;
+            }
+            State::SizeLo => {
+                self.size |= byte as u16;
+                if self.net {
+                    self.state = State::Number;
+                } else {
+                    self.number = 1;
+                    self.count = 1;
+                    self.begin_payload();
+                }
+            }
+            State::Number => {
+                self.number = byte;
+                self.state = State::Count;
+            }
+            State::Count => {
+                self.count = byte;
+                self.begin_payload();
+            }
+            State::Payload => {
+                self.store(byte);
+                self.read += 1;
+                if self.read == self.size {
+                    self.state = State::End;
+                }
+            }
+            State::End => return self.end(byte),
+        }
+
+        Ok(None)
+    }
+
+    /// Waits for the start byte of a packet.
+    fn start(&mut self, byte: u8) {
+        if byte == START || byte == START_NET {
+            self.net = byte == START_NET;
+            self.state = State::Type;
+        }
+    }
+
+    /// Checks the header of the packet before reading its payload.
+    fn begin_payload(&mut self) {
+        self.read = 0;
+        self.error = None;
+
+        if self.kind == TYPE_DATA {
+            // The first packet always starts a new frame, dropping the
+            // one being received, if any.
+            if self.number == 1 {
+                self.offset = 0;
+            }
+            if self.number == 0
+                || self.number > self.count
+                || (self.number != 1 && self.number != self.next_packet)
+            {
+                self.error = Some(Error::OutOfSequence {
+                    expected: self.next_packet,
+                    received: self.number,
+                });
+            }
+
+            if self.offset + self.size as usize > self.pixels.len() * 3 {
+                self.error = self.error.or(Some(Error::Oversized(self.size)));
+            }
+        } else if self.kind == TYPE_COMMAND && self.size as usize > COMMAND_CAPACITY {
+            self.error = Some(Error::Oversized(self.size));
+        }
+
+        self.state = if self.size == 0 {
+            State::End
+        } else {
+            State::Payload
+        };
+    }
+
+    /// Stores a byte of the payload, unless the packet is dropped.
+    fn store(&mut self, byte: u8) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let index = self.read as usize;
+        if self.kind == TYPE_DATA {
+            let index = self.offset + index;
+            self.rgb[index % 3] = byte;
+            if index % 3 == 2 {
+                self.pixels[index / 3] = O::from_rgb(self.rgb[0], self.rgb[1], self.rgb[2]);
+            }
+        } else if self.kind == TYPE_COMMAND {
+            self.command[index] = byte;
+        }
+    }
+
+    /// Checks the end byte, completing the packet.
+    fn end(&mut self, byte: u8) -> Result<Option<Packet>, Error> {
+        self.state = State::Start;
+        if byte != END {
+            // The packet was shorter than its size, so this byte is
+            // past the start of the next one, unless only the end byte
+            // was lost.
+            self.next_packet = 1;
+            self.start(byte);
+            return Err(Error::Short);
+        }
+
+        if let Some(error) = self.error.take() {
+            self.next_packet = 1;
+            return Err(error);
+        }
+
+        match self.kind {
+            TYPE_DATA if self.number < self.count => {
+                self.next_packet = self.number + 1;
+                self.offset += self.size as usize;
+                Ok(None)
+            }
+            TYPE_DATA => {
+                self.next_packet = 1;
+                Ok(Some(Packet::Frame((self.offset + self.size as usize) / 3)))
+            }
+            TYPE_COMMAND => {
+                self.command_len = self.size as usize;
+                Ok(Some(Packet::Command))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /**
+     * Reads the bytes available from `serial` until a packet is
+     * complete, returning it. Returns [nb::Error::WouldBlock] when no
+     * more bytes are available, so it can be called again later to
+     * continue with the same packet.
+     */
+    pub fn receive<S: Read<u8>>(
+        &mut self,
+        serial: &mut S,
+    ) -> nb::Result<Packet, ReceiveError<S::Error>> {
+        loop {
+            let byte = serial.read().map_err(|err| match err {
+                nb::Error::WouldBlock => nb::Error::WouldBlock,
+                nb::Error::Other(err) => nb::Error::Other(ReceiveError::Serial(err)),
+            })?;
+
+            match self.push(byte) {
+                Ok(Some(packet)) => return Ok(packet),
+                Ok(None) => {}
+                Err(err) => return Err(nb::Error::Other(ReceiveError::Protocol(err))),
+            }
+        }
+    }
+
+    /**
+     * Reads the bytes available from `serial` until a packet is
+     * complete and, if it completes a frame, writes its leds into the
+     * driver. The packet is returned, so commands can be handled.
+     */
+    pub fn update<S: Read<u8>, P: StaticPin, Ts: TypedTimings>(
+        &mut self,
+        serial: &mut S,
+        driver: &mut WS2812<P, Ts, O>,
+    ) -> nb::Result<Packet, ReceiveError<S::Error>> {
+        let packet = self.receive(serial)?;
+        if let Packet::Frame(leds) = packet {
+            driver.write(&self.pixels[..leds]);
+        }
+        Ok(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::RGB;
+    use crate::{Dummy, Replay};
+
+    /// Three leds, red, green and blue.
+    const FRAME: &[u8] = &[
+        0xC9, 0xDA, 0x00, 0x09, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0x36,
+    ];
+
+    /// Dark red, dark green and dark blue, in another frame.
+    const NEXT_FRAME: &[u8] = &[
+        0xC9, 0xDA, 0x00, 0x09, 0x80, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00, 0x80, 0x36,
+    ];
+
+    /// The frame of [NEXT_FRAME], split into two TPM2.net packets.
+    const NET_FRAME: &[u8] = &[
+        0x9C, 0xDA, 0x00, 0x06, 0x01, 0x02, 0x80, 0x00, 0x00, 0x00, 0x80, 0x00, 0x36, 0x9C, 0xDA,
+        0x00, 0x03, 0x02, 0x02, 0x00, 0x00, 0x80, 0x36,
+    ];
+
+    fn colors(pixels: &[RGB]) -> Vec<(u8, u8, u8)> {
+        pixels.iter().map(|pixel| pixel.to_rgb()).collect()
+    }
+
+    fn frame_colors(frame: &[u8]) -> Vec<(u8, u8, u8)> {
+        frame[4..frame.len() - 1]
+            .chunks(3)
+            .map(|c| (c[0], c[1], c[2]))
+            .collect()
+    }
+
+    /// Pushes every byte of the stream, returning the packets and the
+    /// errors found.
+    fn parse(receiver: &mut Tpm2<RGB>, stream: &[u8]) -> Vec<Result<Packet, Error>> {
+        stream
+            .iter()
+            .filter_map(|&byte| receiver.push(byte).transpose())
+            .collect()
+    }
+
+    #[test]
+    fn frames() {
+        let stream = [b"\x00\x36garbage".as_slice(), FRAME, NET_FRAME].concat();
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Tpm2::new(&mut pixels);
+        let mut driver = WS2812::<Dummy, Dummy, RGB>::default();
+        let mut serial = Replay(&stream);
+
+        assert_eq!(
+            receiver.update(&mut serial, &mut driver),
+            Ok(Packet::Frame(3))
+        );
+        assert_eq!(
+            receiver.update(&mut serial, &mut driver),
+            Ok(Packet::Frame(3))
+        );
+        assert_eq!(
+            receiver.update(&mut serial, &mut driver),
+            Err(nb::Error::WouldBlock)
+        );
+        let frames: Vec<_> = driver.frames.iter().map(|frame| colors(frame)).collect();
+        assert_eq!(frames, [frame_colors(FRAME), frame_colors(NEXT_FRAME)]);
+    }
+
+    #[test]
+    fn commands() {
+        let stream = [
+            0xC9, 0xC0, 0x00, 0x02, 0x0A, 0x0B, 0x36, 0xC9, 0xC0, 0x00, 0x00, 0x36,
+        ];
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Tpm2::new(&mut pixels);
+        let mut serial = Replay(&stream);
+
+        assert_eq!(receiver.receive(&mut serial), Ok(Packet::Command));
+        assert_eq!(receiver.command(), [0x0A, 0x0B]);
+        assert_eq!(receiver.receive(&mut serial), Ok(Packet::Command));
+        assert_eq!(receiver.command(), []);
+    }
+
+    #[test]
+    fn responses_are_skipped() {
+        let stream = [[0xC9, 0xAA, 0x00, 0x01, 0x00, 0x36].as_slice(), FRAME].concat();
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Tpm2::new(&mut pixels);
+
+        assert_eq!(parse(&mut receiver, &stream), [Ok(Packet::Frame(3))]);
+    }
+
+    #[test]
+    fn oversized() {
+        let mut command = vec![0xC9, 0xC0, 0x00, 0x11];
+        command.extend([0x00; 17]);
+        command.push(0x36);
+        let mut frame = vec![0xC9, 0xDA, 0x00, 0x0C];
+        frame.extend([0xFF; 12]);
+        frame.push(0x36);
+        let stream = [&command, &frame, NEXT_FRAME].concat();
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Tpm2::new(&mut pixels);
+
+        let results = parse(&mut receiver, &stream);
+        assert_eq!(
+            results,
+            [
+                Err(Error::Oversized(17)),
+                Err(Error::Oversized(12)),
+                Ok(Packet::Frame(3))
+            ]
+        );
+        assert_eq!(colors(receiver.pixels()), frame_colors(NEXT_FRAME));
+    }
+
+    #[test]
+    fn out_of_sequence() {
+        let (first, second) = NET_FRAME.split_at(13);
+        // The first packet again, with a packet number over the count.
+        let mut third = first.to_vec();
+        third[4] = 3;
+        let stream = [second, first, first, second, &third].concat();
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Tpm2::new(&mut pixels);
+
+        let results = parse(&mut receiver, &stream);
+        assert_eq!(
+            results,
+            [
+                Err(Error::OutOfSequence {
+                    expected: 1,
+                    received: 2
+                }),
+                Ok(Packet::Frame(3)),
+                Err(Error::OutOfSequence {
+                    expected: 1,
+                    received: 3
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_type() {
+        let stream = [[0xC9, 0x01, 0x00, 0x00, 0x36].as_slice(), FRAME].concat();
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Tpm2::new(&mut pixels);
+
+        let results = parse(&mut receiver, &stream);
+        assert_eq!(
+            results,
+            [Err(Error::UnknownType(0x01)), Ok(Packet::Frame(3))]
+        );
+    }
+
+    #[test]
+    fn lost_end_byte() {
+        let stream = [&FRAME[..FRAME.len() - 1], NEXT_FRAME].concat();
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Tpm2::new(&mut pixels);
+
+        let results = parse(&mut receiver, &stream);
+        assert_eq!(results, [Err(Error::Short), Ok(Packet::Frame(3))]);
+        assert_eq!(colors(receiver.pixels()), frame_colors(NEXT_FRAME));
+    }
+
+    #[test]
+    fn short_packet_loses_the_next_one() {
+        // The first packet lost a led, so the start of the second one
+        // is taken as its payload.
+        let mut stream = [FRAME, NEXT_FRAME, FRAME].concat();
+        stream.drain(7..10);
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Tpm2::new(&mut pixels);
+
+        let results = parse(&mut receiver, &stream);
+        assert_eq!(results, [Err(Error::Short), Ok(Packet::Frame(3))]);
+        assert_eq!(colors(receiver.pixels()), frame_colors(FRAME));
+    }
+
+    #[test]
+    fn short_packet_with_start_byte_in_next_payload() {
+        // After the short packet, the start byte in the payload of the
+        // lost packet is taken as the start of a packet.
+        let lost = [0xC9, 0xDA, 0x00, 0x03, 0xC9, 0x00, 0x00, 0x36];
+        let mut stream = [FRAME, &lost, NEXT_FRAME].concat();
+        stream.drain(4..7);
+        let mut pixels = [RGB::default(); 3];
+        let mut receiver = Tpm2::new(&mut pixels);
+
+        let results = parse(&mut receiver, &stream);
+        assert_eq!(
+            results,
+            [
+                Err(Error::Short),
+                Err(Error::UnknownType(0x00)),
+                Ok(Packet::Frame(3))
+            ]
+        );
+        assert_eq!(colors(receiver.pixels()), frame_colors(NEXT_FRAME));
+    }
+}
@@ -0,0 +1,324 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Transitions between two frames, or between two running effects,
+ * that replace abrupt scene changes with crossfades, wipes or
+ * dissolves. All the math is done with 8 and 16-bit integers. */
+use crate::color::ColorOrder;
+use crate::effects::Effect;
+use crate::math8::{ease8, ColorMath};
+
+/// The way a transition goes from the first frame to the second one.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TransitionStyle {
+    /// Crossfades the frames at a constant speed.
+    Linear,
+    /// Crossfades the frames, starting and ending slowly.
+    EaseInOut,
+    /// Replaces the leds one after the other, from the start of the
+    /// strip to its end.
+    Wipe,
+    /// Replaces the leds one at a time, in a random-looking order.
+    Dissolve,
+}
+
+/**
+ * A transition that takes `duration` frames. Every call to
+ * [Transition::step] renders a frame of the transition and moves it
+ * forward, until it is done and only shows the second frame.
+ */
+#[derive(Clone, Copy)]
+pub struct Transition {
+    style: TransitionStyle,
+    duration: u16,
+    elapsed: u16,
+}
+
+impl Transition {
+    pub const fn new(style: TransitionStyle, duration: u16) -> Self {
+        Transition {
+            style,
+            duration,
+            elapsed: 0,
+        }
+    }
+
+    /// Starts the transition again from the first frame.
+    pub fn restart(&mut self) {
+        self.elapsed = 0;
+    }
+
+    /// Returns whether the transition has finished.
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// Returns how far the transition has gone, from 0 at its start to
+    /// 255 when it is done.
+    pub fn progress(&self) -> u8 {
+        if self.is_done() {
+            255
+        } else {
+            (self.elapsed as u32 * 255 / self.duration as u32) as u8
+        }
+    }
+
+    /**
+     * Renders the current frame of the transition from `from` to `to`
+     * into `target`, without moving it forward. Only the leds present
+     * in the three buffers are rendered.
+     */
+    pub fn render<O: ColorOrder>(&self, from: &[O], to: &[O], target: &mut [O]) {
+        let len = target.len().min(from.len()).min(to.len());
+        let progress = self.progress();
+
+        if progress == 255 {
+            target[..len].copy_from_slice(&to[..len]);
+            return;
+        }
+
+        match self.style {
+            TransitionStyle::Linear | TransitionStyle::EaseInOut => {
+                let amount = if self.style == TransitionStyle::EaseInOut {
+                    ease8(progress)
+                } else {
+                    progress
+                };
+                for ((pixel, from), to) in target.iter_mut().zip(from).zip(to) {
+                    *pixel = from.blend(*to, amount);
+                }
+            }
+            TransitionStyle::Wipe => {
+                let edge = len * progress as usize / 255;
+                target[..edge].copy_from_slice(&to[..edge]);
+                target[edge..len].copy_from_slice(&from[edge..len]);
+            }
+            TransitionStyle::Dissolve => {
+                let pixels = target.iter_mut().zip(from).zip(to);
+                for (i, ((pixel, from), to)) in pixels.enumerate() {
+                    *pixel = if dissolve_threshold(i) < progress {
+                        *to
+                    } else {
+                        *from
+                    };
+                }
+            }
+        }
+    }
+
+    /// Moves the transition one frame forward.
+    pub fn advance(&mut self) {
+        if !self.is_done() {
+            self.elapsed += 1;
+        }
+    }
+
+    /// Renders the current frame of the transition into `target`, and
+    /// moves it one frame forward. See [Transition::render].
+    pub fn step<O: ColorOrder>(&mut self, from: &[O], to: &[O], target: &mut [O]) {
+        self.render(from, to, target);
+        self.advance();
+    }
+}
+
+/// Progress at which a led is replaced by a dissolve. The leds are
+/// hashed so that neighbouring ones get unrelated thresholds.
+fn dissolve_threshold(i: usize) -> u8 {
+    let mut x = (i as u16).wrapping_mul(40503).wrapping_add(0x9E37);
+    x ^= x >> 7;
+    x = x.wrapping_mul(2053);
+    (x >> 8) as u8 ^ x as u8
+}
+
+/**
+ * An effect that runs two effects at once, transitioning from the
+ * first one to the second one. It keeps a buffer of `N` leds for each
+ * of them, so it takes twice the memory of the strip.
+ */
+pub struct EffectTransition<A, B, O, const N: usize> {
+    from: A,
+    to: B,
+    from_pixels: [O; N],
+    to_pixels: [O; N],
+    transition: Transition,
+}
+
+impl<A: Effect<O>, B: Effect<O>, O: ColorOrder, const N: usize> EffectTransition<A, B, O, N> {
+    pub fn new(from: A, to: B, transition: Transition) -> Self {
+        EffectTransition {
+            from,
+            to,
+            from_pixels: [O::from_rgb(0, 0, 0); N],
+            to_pixels: [O::from_rgb(0, 0, 0); N],
+            transition,
+        }
+    }
+
+    /// Returns whether the transition has finished.
+    pub fn is_done(&self) -> bool {
+        self.transition.is_done()
+    }
+
+    /// Destroys the transition, returning the effect it went to, which
+    /// can keep running on its own once the transition is done.
+    pub fn into_target(self) -> B {
+        self.to
+    }
+}
+
+impl<A: Effect<O>, B: Effect<O>, O: ColorOrder, const N: usize> Effect<O>
+    for EffectTransition<A, B, O, N>
+{
+    fn step(&mut self, pixels: &mut [O], tick: u32) {
+        // Once done, the first effect is no longer needed.
+        if !self.transition.is_done() {
+            self.from.step(&mut self.from_pixels, tick);
+        }
+        self.to.step(&mut self.to_pixels, tick);
+        self.transition
+            .step(&self.from_pixels, &self.to_pixels, pixels);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::RGB;
+
+    const FROM: RGB = RGB::from_rgb(10, 20, 30);
+    const TO: RGB = RGB::from_rgb(200, 100, 0);
+
+    /// An effect that fills the leds with its color, counting its steps.
+    struct Fill(RGB, u32);
+
+    impl Effect<RGB> for Fill {
+        fn step(&mut self, pixels: &mut [RGB], _tick: u32) {
+            pixels.fill(self.0);
+            self.1 += 1;
+        }
+    }
+
+    fn render(transition: &Transition, leds: usize) -> Vec<RGB> {
+        let mut target = vec![RGB::default(); leds];
+        transition.render(&vec![FROM; leds], &vec![TO; leds], &mut target);
+        target
+    }
+
+    fn is(pixels: &[RGB], color: RGB) -> bool {
+        pixels.iter().all(|pixel| pixel.to_rgb() == color.to_rgb())
+    }
+
+    #[test]
+    fn progress() {
+        let mut transition = Transition::new(TransitionStyle::Linear, 4);
+        let mut progress = Vec::new();
+        for _ in 0..6 {
+            progress.push((transition.progress(), transition.is_done()));
+            transition.advance();
+        }
+        assert_eq!(
+            progress,
+            [
+                (0, false),
+                (63, false),
+                (127, false),
+                (191, false),
+                (255, true),
+                (255, true)
+            ]
+        );
+
+        transition.restart();
+        assert_eq!(transition.progress(), 0);
+        assert!(!transition.is_done());
+    }
+
+    #[test]
+    fn zero_duration() {
+        let transition = Transition::new(TransitionStyle::Linear, 0);
+        assert!(transition.is_done());
+        assert_eq!(transition.progress(), 255);
+        assert!(is(&render(&transition, 3), TO));
+    }
+
+    #[test]
+    fn crossfades() {
+        for style in [TransitionStyle::Linear, TransitionStyle::EaseInOut] {
+            let mut transition = Transition::new(style, 2);
+            assert!(is(&render(&transition, 3), FROM));
+            transition.advance();
+            let middle = render(&transition, 3)[0].to_rgb();
+            assert!(middle.0 > FROM.to_rgb().0 && middle.0 < TO.to_rgb().0);
+            transition.advance();
+            assert!(is(&render(&transition, 3), TO));
+        }
+    }
+
+    #[test]
+    fn wipe() {
+        let mut transition = Transition::new(TransitionStyle::Wipe, 4);
+        let mut edges = Vec::new();
+        while !transition.is_done() {
+            let pixels = render(&transition, 8);
+            let edge = pixels.iter().take_while(|pixel| is(&[**pixel], TO)).count();
+            assert!(is(&pixels[edge..], FROM));
+            edges.push(edge);
+            transition.advance();
+        }
+        assert_eq!(edges, [0, 1, 3, 5]);
+        assert!(is(&render(&transition, 8), TO));
+    }
+
+    #[test]
+    fn dissolve() {
+        const LEDS: usize = 64;
+        let mut transition = Transition::new(TransitionStyle::Dissolve, 100);
+        let mut switched = [0; LEDS];
+        let mut previous = render(&transition, LEDS);
+        assert!(is(&previous, FROM));
+
+        while !transition.is_done() {
+            transition.advance();
+            let pixels = render(&transition, LEDS);
+            for (i, (before, after)) in previous.iter().zip(&pixels).enumerate() {
+                if before.to_rgb() != after.to_rgb() {
+                    assert!(is(&[*after], TO));
+                    switched[i] += 1;
+                }
+            }
+            previous = pixels;
+        }
+
+        assert!(is(&previous, TO));
+        assert_eq!(switched, [1; LEDS]);
+    }
+
+    #[test]
+    fn effects() {
+        let transition = Transition::new(TransitionStyle::Linear, 2);
+        let mut effect =
+            EffectTransition::<_, _, RGB, 3>::new(Fill(FROM, 0), Fill(TO, 0), transition);
+        let mut pixels = [RGB::default(); 3];
+
+        for tick in 0..5 {
+            effect.step(&mut pixels, tick);
+        }
+        assert!(effect.is_done());
+        assert!(is(&pixels, TO));
+        assert_eq!(effect.from.1, 2);
+        assert_eq!(effect.into_target().1, 5);
+    }
+}
@@ -168,6 +168,137 @@ pub const fn num_to_string<const LEN: usize>(value: NumLengthed<LEN>) -> ConstSt
     ConstStr { data: buf }
 }
 
+/**
+Parses an unsigned decimal number from a string in const context,
+panicking if the string is not a valid number.
+*/
+pub const fn parse_u32(s: &str) -> u32 {
+    let bytes = s.as_bytes();
+    if bytes.is_empty() {
+        panic!("Cannot parse an empty string as a number");
+    }
+
+    let mut value: u32 = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let digit = bytes[i];
+        if digit < b'0' || digit > b'9' {
+            panic!("Invalid digit found while parsing a number");
+        }
+
+        value = match value.checked_mul(10) {
+            Some(v) => v,
+            None => panic!("Number too large"),
+        };
+        value = match value.checked_add((digit - b'0') as u32) {
+            Some(v) => v,
+            None => panic!("Number too large"),
+        };
+        i += 1;
+    }
+
+    value
+}
+
+/**
+A string built at compile time into a buffer of a fixed capacity, for
+strings whose length is not known in advance, like the ones that
+depend on generic parameters. The bytes up to its length are always
+a valid UTF-8 string.
+*/
+#[derive(Clone, Copy)]
+pub struct ConstStrBuf<const CAP: usize> {
+    data: [u8; CAP],
+    len: usize,
+}
+
+impl<const CAP: usize> ConstStrBuf<CAP> {
+    /**
+    Creates an empty string.
+    */
+    pub const fn new() -> Self {
+        ConstStrBuf {
+            data: [0; CAP],
+            len: 0,
+        }
+    }
+
+    /**
+    Appends a string, panicking if it does not fit into the buffer.
+    */
+    pub const fn push_str(mut self, s: &str) -> Self {
+        let bytes = s.as_bytes();
+        if self.len + bytes.len() > CAP {
+            panic!("The string does not fit into the buffer");
+        }
+
+        let mut i = 0;
+        while i < bytes.len() {
+            self.data[self.len + i] = bytes[i];
+            i += 1;
+        }
+        self.len += bytes.len();
+        self
+    }
+
+    /**
+    Appends the decimal representation of an unsigned number,
+    panicking if it does not fit into the buffer.
+    */
+    pub const fn push_num(mut self, mut num: u64) -> Self {
+        let mut digits = [0u8; 20];
+        let mut count = 0;
+        while {
+            digits[count] = b'0' + (num % 10) as u8;
+            num /= 10;
+            count += 1;
+            num > 0
+        } {}
+
+        if self.len + count > CAP {
+            panic!("The string does not fit into the buffer");
+        }
+
+        while count > 0 {
+            count -= 1;
+            self.data[self.len] = digits[count];
+            self.len += 1;
+        }
+        self
+    }
+
+    /**
+    Returns the length of the string built so far, in bytes.
+    */
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /**
+    Returns whether the string built so far is empty.
+    */
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /**
+    Moves the string into a buffer of another capacity, panicking if it
+    does not fit into it.
+    */
+    pub const fn resize<const NEW_CAP: usize>(self) -> ConstStrBuf<NEW_CAP> {
+        ConstStrBuf::new().push_str(self.as_str())
+    }
+
+    /**
+    Returns the string built so far.
+    */
+    pub const fn as_str(&self) -> &str {
+        // SAFETY: Only whole strings and ASCII digits are appended, so
+        // the bytes up to the length are valid UTF-8.
+        unsafe { core::str::from_utf8_unchecked(&self.data[..self.len]) }
+    }
+}
+
 /**
 Concats two constant arguments into a [ConstStr].
 */
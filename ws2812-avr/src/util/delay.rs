@@ -0,0 +1,49 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+use core::arch::asm;
+
+/**
+Busy-waits for at least the given number of CPU cycles. The waiting
+is done in a loop whose iterations take 4 cycles each (`sbiw` plus a
+taken `brne`), so the actual delay is rounded up to the next multiple
+of 4 cycles, plus the overhead of the surrounding code.
+*/
+#[inline(always)]
+pub fn delay_cycles(cycles: u32) {
+    let mut iterations = cycles / 4 + 1;
+
+    while iterations > 0 {
+        let chunk: u16 = if iterations > 0xFFFF {
+            0xFFFF
+        } else {
+            iterations as u16
+        };
+
+        // SAFETY: The loop only touches the register that holds the
+        // counter, which is discarded afterwards.
+        unsafe {
+            asm!(
+                "2:",
+                "sbiw {n}, 1",
+                "brne 2b",
+                n = inout(reg_iw) chunk => _,
+            );
+        }
+
+        iterations -= chunk as u32;
+    }
+}
@@ -16,7 +16,9 @@ along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
  */
 pub mod asm;
 pub mod const_str;
+pub mod delay;
 pub mod nop_block;
+pub mod progmem;
 pub mod time;
 pub use nop_block::*;
 
@@ -0,0 +1,139 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+/*! Access to data stored in the program memory (flash) of the device.
+ * On AVR, data is copied into RAM on startup unless it is placed into
+ * the `.progmem.data` section, from where it can only be read by
+ * using the `lpm` instruction.
+ *
+ * `lpm` only reaches the first 64 KiB of the program memory, which is
+ * all that the 16-bit pointers of AVR can address. The linker places
+ * `.progmem.data` at the start of the flash, so on devices with more
+ * than 64 KiB of it, such as the ATmega1280, ATmega2560 and
+ * ATmega1284P, the data declared with [crate::progmem] must fit into
+ * the first 64 KiB, along with the interrupt vectors. */
+#[cfg(target_arch = "avr")]
+use core::arch::asm;
+use core::mem::{size_of, MaybeUninit};
+
+/**
+Wraps a value that is stored in the program memory. The wrapper must
+be placed into the program memory itself, otherwise reading it would
+return garbage, so it is declared through the [crate::progmem] macro:
+
+```no_run
+progmem! {
+    static DATA: [u8; 4] = [1, 2, 3, 4];
+}
+```
+*/
+#[repr(transparent)]
+pub struct ProgMem<T> {
+    value: T,
+}
+
+// SAFETY: The value is never mutated, as the program memory cannot be
+// written at runtime.
+unsafe impl<T> Sync for ProgMem<T> {}
+
+impl<T: Copy> ProgMem<T> {
+    /**
+    Wraps a value. This is unsafe because the caller must make sure
+    that the wrapper is a static placed into the `.progmem.data`
+    section, which is what the [crate::progmem] macro does.
+    */
+    pub const unsafe fn new(value: T) -> Self {
+        ProgMem { value }
+    }
+
+    /// Reads the whole value from the program memory.
+    #[inline(always)]
+    pub fn load(&self) -> T {
+        // SAFETY: The offset is zero and the read covers T entirely.
+        unsafe { self.read_at(0) }
+    }
+
+    /**
+    Reads a value of type `U` located at the given byte offset from the
+    beginning of the wrapped value. The caller must make sure that the
+    read lies within the wrapped value, and that the bytes read form a
+    valid `U`. Panics if the read crosses the end of the first 64 KiB
+    of the program memory, which `lpm` can't reach.
+    */
+    #[inline(always)]
+    pub unsafe fn read_at<U: Copy>(&self, offset: usize) -> U {
+        let mut result = MaybeUninit::<U>::uninit();
+        let src = (&self.value as *const T as *const u8).add(offset);
+        // Addresses are 16 bits wide on AVR, so a read beyond the first
+        // 64 KiB wraps around.
+        assert!(
+            (src as usize)
+                .checked_add(size_of::<U>().saturating_sub(1))
+                .is_some(),
+            "Program memory beyond 64 KiB can't be read."
+        );
+        let dst = result.as_mut_ptr() as *mut u8;
+
+        let mut i = 0;
+        while i < size_of::<U>() {
+            *dst.add(i) = read_byte(src.add(i));
+            i += 1;
+        }
+
+        result.assume_init()
+    }
+}
+
+/// Reads a single byte from the given address of the program memory.
+#[inline(always)]
+unsafe fn read_byte(addr: *const u8) -> u8 {
+    #[cfg(target_arch = "avr")]
+    {
+        let value: u8;
+        asm!("lpm {value}, Z", value = out(reg) value, in("Z") addr);
+        value
+    }
+
+    #[cfg(not(target_arch = "avr"))]
+    {
+        *addr
+    }
+}
+
+/**
+Declares statics that are stored in the program memory, wrapped into
+[ProgMem]. The type given is the one of the wrapped value:
+
+```no_run
+progmem! {
+    static PALETTE: Palette16<GRB> = palette::LAVA.convert();
+    pub static GREETING: [u8; 5] = *b"hello";
+}
+```
+*/
+#[macro_export]
+macro_rules! progmem {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $value:expr;)*) => {
+	$(
+	    $(#[$attr])*
+	    #[link_section = ".progmem.data"]
+	    $vis static $name: $crate::util::progmem::ProgMem<$ty> =
+		// SAFETY: The static is placed into the program memory.
+		unsafe { $crate::util::progmem::ProgMem::new($value) };
+	)*
+    };
+}
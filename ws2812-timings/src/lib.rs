@@ -0,0 +1,328 @@
+/*
+This file is part of ws2812-avr.
+
+ws2812-avr is free software: you can redistribute it and/or modify it
+under the terms of the GNU General Public License as published by the
+Free Software Foundation, either version 3 of the License, or (at your
+option) any later version.
+
+ws2812-avr is distributed in the hope that it will be useful, but
+WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with ws2812-avr. If not, see <https://www.gnu.org/licenses/>.
+ */
+/*! Timing calculations of the ws2812-avr driver, shared by the library
+ * and the host tools so that both always agree. Everything is
+ * computed with integer math in const functions, from the CPU
+ * frequency and the timings in nanoseconds. */
+#![no_std]
+
+/** The number of fixed cycles (non-nop instructions) consumed by
+the instructions of the loop after enabling the pin until it is
+disabled, when the value of the bit that is being set is zero. The
+current value is based on the behavior of this code, which is in
+charge of enabling the pin during data sending:
+```text
+...
+pin_high();                      // Pin is enabled just after this instruction, so this cycle is not counted.
+Ts::S1Nops::gen();               // Generates a variable number of NOP instructions, not counted.
+skip_if_bit_set!(curbyte, "7");  // sbrs instruction. Takes 2 cycles when bit is set, 1 when not. Therefore, it counts as 1.
+pin_low();                       // Pin is disabled just after this instruction, so it counts as 1.
+...
+```
+ */
+pub const FIXED_CYCLES_T0H: u8 = 2;
+
+/**
+The number of fixed cycles (non-nop instructions) consumed by the
+instructions of the loop after enabling the pin until it is
+disabled, when the value of the bit that is being set is one. The
+current value is based on the behavior of this code, which is in
+charge of enabling the pin during data sending:
+```text
+...
+pin_high();                      // Pin is enabled just after this instruction, so this cycle is not counted.
+Ts::S1Nops::gen();               // Generates a variable number of NOP instructions, not counted.
+skip_if_bit_set!(curbyte, "7");  // sbrs instruction. Takes 2 cycles when bit is set, 1 when not. Therefore, it counts as 2.
+pin_low();                       // Instruction skipped by previous instruction.
+lsl!(curbyte);                   // lsl instruction, takes 1 cycle.
+Ts::S2Nops::gen();               // Generates a variable number of NOP instructions, not counted.
+pin_low();                       // Pin is disabled just after this instruction, so it counts as 1.
+...
+```
+*/
+pub const FIXED_CYCLES_T1H: u8 = 4;
+
+/**
+The number of fixed cycles (non-nop instructions) that takes the
+program to give a full loop through the code in charge of send
+color signals to the device. The current value for this constant
+is calculated by counting the fixed instructions from the code in
+charge of sending this data to the device:
+```text
+...
+2: pin_high();                   // When the code loops, it ends here putting the pin high again. So it counts as 1 cycle.
+Ts::S1Nops::gen();               // Generates a variable number of NOP instructions, not counted.
+skip_if_bit_set!(curbyte, "7");  // This and the next instruction
+                                 // always count as two cycles: if the next instruction is skipped,
+                                 // then the sbrs instruction takes 2 cycles. If it is not skipped,
+                                 // sbrs takes 1 cycle and the next one takes 1 cycle as well.
+pin_low();
+lsl!(curbyte);                   // Takes 1 instruction.
+Ts::S2Nops::gen();               // Generates a variable number of NOP instructions, not counted.
+pin_low();                       // Takes 1 instruction.
+Ts::S3Nops::gen();               // Generates a variable number of NOP instructions, not counted.
+dec!(i);                         // Takes 1 instruction.
+branch_not_equal!("2b");         // Takes 2 instructions when the loop continues.
+...
+```
+*/
+pub const FIXED_CYCLES_TOTAL: u8 = 8;
+
+/**
+Extra cycles that it takes to move on to the next byte after sending the
+last bit of a byte, when writing a slice with `WS2812::write`. Unlike the
+other fixed cycles, the outer loop of the driver is generated by the
+compiler rather than written in assembly, so this constant is derived
+from the assembly that rustc generates for it:
+```text
+...
+branch_not_equal!("2b");         // Not taken after the last bit, so it takes 1 cycle less.
+len -= 1;                        // sbiw instruction, takes 2 cycles.
+while len > 0 { ... }            // cpi, cpc, breq and rjmp instructions, take 5 cycles.
+curbyte = *data;                 // ld instruction with post-increment, takes 2 cycles.
+ld_immediate!(i, "8");           // ldi instruction, takes 1 cycle.
+...
+```
+That is 11 cycles, less the 2 cycles of the taken branch which are already
+counted in [FIXED_CYCLES_TOTAL]. Other compiler versions or optimization
+levels may generate a different loop; the simavr harness in
+`tools/simavr` measures the gaps of the driver, so it catches a change.
+
+The paths built on `WS2812::write_iter` (`write_scaled`, `write_indexed`,
+`write_limited` and the smart-leds `write`) compute the next pixel
+between pixels, so their gaps are longer, and depend on the work done
+by the iterator. Timings computed with this constant are thus only a
+lower bound for them.
+*/
+pub const FIXED_CYCLES_BYTE: u8 = 9;
+
+const NANOS_IN_SECOND: u64 = 1000000000;
+
+macro_rules! diff_clamp_zero {
+    ($a:expr, $b: expr) => {
+	if ($a) >= ($b) {
+	    ($a) - ($b)
+	} else {
+	    0
+	}
+    };
+
+    ($a:expr, $b: expr, $($c:expr),*) => {
+	if ($a) >= ($b) {
+	    diff_clamp_zero!(($a) - ($b), $($c),*)
+	} else {
+	    0
+	}
+    };
+}
+
+/**
+ * The values derived from a set of timings at a CPU frequency: the
+ * number of cycles of each part of a bit, and the number of NOPs that
+ * are inserted at each stage of the loop for reaching them.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Calculated {
+    /// Total number of cycles required the T0H signal to be enabled.
+    pub t0h_cycles: u8,
+    /// Total number of cycles required the T1H signal to be enabled.
+    pub t1h_cycles: u8,
+    /// Total number of CPU cycles that it takes to run a full cycle
+    /// of enabling and disabling the pin.
+    pub total_cycles: u8,
+    /// Total number of CPU cycles that the reset time takes.
+    pub rst_cycles: u32,
+    /// Number of NOP cycles at stage 1.
+    pub s1_nops: u8,
+    /// Number of NOP cycles at stage 2.
+    pub s2_nops: u8,
+    /// Number of NOP cycles at stage 3.
+    pub s3_nops: u8,
+}
+
+impl Calculated {
+    /// Number of cycles the pin is actually kept high for a zero bit.
+    pub const fn achieved_t0h_cycles(&self) -> u32 {
+        FIXED_CYCLES_T0H as u32 + self.s1_nops as u32
+    }
+
+    /// Number of cycles the pin is actually kept high for a one bit.
+    pub const fn achieved_t1h_cycles(&self) -> u32 {
+        FIXED_CYCLES_T1H as u32 + self.s1_nops as u32 + self.s2_nops as u32
+    }
+
+    /// Number of CPU cycles that sending a bit actually takes, which
+    /// may be longer than `total_cycles` when the fixed cycles of the
+    /// loop do not fit into it.
+    pub const fn bit_cycles(&self) -> u32 {
+        FIXED_CYCLES_TOTAL as u32 + self.s1_nops as u32 + self.s2_nops as u32 + self.s3_nops as u32
+    }
+}
+
+/**
+ * Computes the cycles and NOPs for the given timings, in nanoseconds,
+ * at a CPU frequency of `f_cpu` Hz.
+ */
+pub const fn calculate(f_cpu: u32, t0h: u64, t1h: u64, cycle: u64, rst: u64) -> Calculated {
+    let t0h_cycles = nanos_to_cycles(f_cpu, t0h) as u8;
+    let t1h_cycles = nanos_to_cycles(f_cpu, t1h) as u8;
+    let total_cycles = nanos_to_cycles(f_cpu, cycle) as u8;
+    let rst_cycles = nanos_to_cycles(f_cpu, rst) as u32;
+
+    let s1_nops: u8 = diff_clamp_zero!(t0h_cycles, FIXED_CYCLES_T0H);
+    let s2_nops: u8 = diff_clamp_zero!(t1h_cycles, FIXED_CYCLES_T1H, s1_nops);
+    let s3_nops: u8 = diff_clamp_zero!(total_cycles, FIXED_CYCLES_TOTAL, s1_nops, s2_nops);
+
+    Calculated {
+        t0h_cycles,
+        t1h_cycles,
+        total_cycles,
+        rst_cycles,
+        s1_nops,
+        s2_nops,
+        s3_nops,
+    }
+}
+
+/// Number of whole CPU cycles that fit into the given nanoseconds.
+pub const fn nanos_to_cycles(f_cpu: u32, nanos: u64) -> u64 {
+    f_cpu as u64 * nanos / NANOS_IN_SECOND
+}
+
+/// Nanoseconds that the given number of CPU cycles take, rounded down.
+pub const fn cycles_to_nanos(f_cpu: u32, cycles: u64) -> u64 {
+    cycles * NANOS_IN_SECOND / f_cpu as u64
+}
+
+/// Estimated number of CPU cycles that it takes to send `bytes` bytes,
+/// when every bit takes `bit_cycles` cycles, without the reset pause.
+/// See [FIXED_CYCLES_BYTE] for the gaps between bytes it assumes, and
+/// the write paths it is only a lower bound for.
+pub const fn transmit_cycles_estimate(bit_cycles: u32, bytes: u64) -> u64 {
+    bytes * (8 * bit_cycles as u64 + FIXED_CYCLES_BYTE as u64)
+}
+
+/**
+ * The ranges of timings, in nanoseconds, accepted by a led chip
+ * according to its datasheet.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct ChipTolerance {
+    pub name: &'static str,
+    pub t0h: (u64, u64),
+    pub t1h: (u64, u64),
+    pub t0l: (u64, u64),
+    pub t1l: (u64, u64),
+    /// Shortest low time that latches the data.
+    pub min_rst: u64,
+}
+
+/// Timings achieved by a configuration, in nanoseconds.
+#[derive(Clone, Copy, Debug)]
+pub struct Achieved {
+    pub t0h: u64,
+    pub t1h: u64,
+    pub t0l: u64,
+    pub t1l: u64,
+    /// Time that sending a bit takes.
+    pub cycle: u64,
+    /// Extra low time after the last bit of every byte.
+    pub byte_gap: u64,
+    pub rst: u64,
+}
+
+impl Achieved {
+    /// Computes the timings achieved by a configuration at a CPU
+    /// frequency of `f_cpu` Hz.
+    pub const fn new(f_cpu: u32, calculated: &Calculated) -> Self {
+        let bit = calculated.bit_cycles() as u64;
+        let t0h = calculated.achieved_t0h_cycles() as u64;
+        let t1h = calculated.achieved_t1h_cycles() as u64;
+        Achieved {
+            t0h: cycles_to_nanos(f_cpu, t0h),
+            t1h: cycles_to_nanos(f_cpu, t1h),
+            t0l: cycles_to_nanos(f_cpu, bit - t0h),
+            t1l: cycles_to_nanos(f_cpu, bit - t1h),
+            cycle: cycles_to_nanos(f_cpu, bit),
+            byte_gap: cycles_to_nanos(f_cpu, FIXED_CYCLES_BYTE as u64),
+            rst: cycles_to_nanos(f_cpu, calculated.rst_cycles as u64),
+        }
+    }
+}
+
+impl ChipTolerance {
+    /// Returns whether the high times of the achieved timings are in
+    /// range, along with the low times of all the bits but the last
+    /// of each byte, and the reset pause is long enough. The low time
+    /// of the last bit of each byte is longer by the byte gap.
+    pub const fn accepts(&self, achieved: &Achieved) -> bool {
+        const fn in_range(value: u64, range: (u64, u64)) -> bool {
+            value >= range.0 && value <= range.1
+        }
+
+        in_range(achieved.t0h, self.t0h)
+            && in_range(achieved.t1h, self.t1h)
+            && in_range(achieved.t0l, self.t0l)
+            && in_range(achieved.t1l, self.t1l)
+            && achieved.rst >= self.min_rst
+    }
+}
+
+/// Tolerances of the most common led chips, taken from their
+/// datasheets, with the usual ±150 ns margin where they give one.
+pub const CHIPS: [ChipTolerance; 5] = [
+    ChipTolerance {
+        name: "WS2812",
+        t0h: (200, 500),
+        t1h: (550, 850),
+        t0l: (650, 950),
+        t1l: (450, 750),
+        min_rst: 50_000,
+    },
+    ChipTolerance {
+        name: "WS2812B",
+        t0h: (250, 550),
+        t1h: (650, 950),
+        t0l: (700, 1000),
+        t1l: (300, 600),
+        min_rst: 50_000,
+    },
+    ChipTolerance {
+        name: "WS2813",
+        t0h: (220, 380),
+        t1h: (580, 1000),
+        t0l: (580, 1000),
+        t1l: (220, 420),
+        min_rst: 280_000,
+    },
+    ChipTolerance {
+        name: "SK6812",
+        t0h: (150, 450),
+        t1h: (450, 750),
+        t0l: (750, 1050),
+        t1l: (450, 750),
+        min_rst: 80_000,
+    },
+    ChipTolerance {
+        name: "WS2811",
+        t0h: (100, 400),
+        t1h: (450, 750),
+        t0l: (850, 1150),
+        t1l: (500, 800),
+        min_rst: 50_000,
+    },
+];